@@ -0,0 +1,10 @@
+-- Track modification time and redirect hits for sorting
+ALTER TABLE links ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE links ADD COLUMN hit_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE links SET updated_at = COALESCE(created_at, now());
+
+-- Indexes for keyset pagination on each sort order
+CREATE INDEX idx_links_updated_at ON links (updated_at DESC, id DESC);
+CREATE INDEX idx_links_source ON links (source, id);
+CREATE INDEX idx_links_hit_count ON links (hit_count DESC, id DESC);
@@ -0,0 +1,27 @@
+-- Notify listening instances whenever a link changes so they can update their caches.
+-- Payloads carry only identifiers; listeners fetch the current row themselves, which
+-- keeps long targets clear of the 8000 byte NOTIFY limit. Hit counter updates are not
+-- broadcast, otherwise every redirect would fan out to every instance.
+CREATE OR REPLACE FUNCTION notify_link_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        PERFORM pg_notify('lynx_links', json_build_object('op', TG_OP, 'id', NEW.id)::text);
+        RETURN NEW;
+    ELSIF TG_OP = 'UPDATE' THEN
+        IF (NEW.host, NEW.source, NEW.target) IS NOT DISTINCT FROM (OLD.host, OLD.source, OLD.target) THEN
+            RETURN NEW;
+        END IF;
+        PERFORM pg_notify('lynx_links', json_build_object(
+            'op', TG_OP, 'id', NEW.id, 'old_host', OLD.host, 'old_source', OLD.source)::text);
+        RETURN NEW;
+    ELSE
+        PERFORM pg_notify('lynx_links', json_build_object(
+            'op', TG_OP, 'id', OLD.id, 'old_host', OLD.host, 'old_source', OLD.source)::text);
+        RETURN OLD;
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER links_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON links
+    FOR EACH ROW EXECUTE FUNCTION notify_link_change();
@@ -0,0 +1,13 @@
+-- Earlier targets of links rewritten in bulk, so a find-and-replace can be reviewed and undone
+CREATE TABLE link_revisions (
+    id SERIAL PRIMARY KEY,
+    link_id INTEGER NOT NULL REFERENCES links (id) ON DELETE CASCADE,
+    -- The link's version after the change
+    version INTEGER NOT NULL,
+    old_target TEXT NOT NULL,
+    new_target TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_link_revisions_link_id ON link_revisions (link_id, id DESC);
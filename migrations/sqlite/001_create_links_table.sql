@@ -0,0 +1,18 @@
+-- SQLite schema for single-node deployments, equivalent to the Postgres migrations.
+-- Timestamps are fixed-width UTC strings so they sort correctly as text.
+CREATE TABLE links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    host TEXT NOT NULL,
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    hit_count INTEGER NOT NULL DEFAULT 0,
+
+    UNIQUE (host, source)
+);
+
+CREATE INDEX idx_links_created_at ON links (created_at DESC, id DESC);
+CREATE INDEX idx_links_updated_at ON links (updated_at DESC, id DESC);
+CREATE INDEX idx_links_source ON links (source, id);
+CREATE INDEX idx_links_hit_count ON links (hit_count DESC, id DESC);
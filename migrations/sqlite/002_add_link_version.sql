@@ -0,0 +1,2 @@
+-- A counter bumped on every change to a link, so edits made from a stale copy can be refused
+ALTER TABLE links ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
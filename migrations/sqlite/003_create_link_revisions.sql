@@ -0,0 +1,13 @@
+-- Earlier targets of links rewritten in bulk, so a find-and-replace can be reviewed and undone
+CREATE TABLE link_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    link_id INTEGER NOT NULL REFERENCES links (id) ON DELETE CASCADE,
+    -- The link's version after the change
+    version INTEGER NOT NULL,
+    old_target TEXT NOT NULL,
+    new_target TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE INDEX idx_link_revisions_link_id ON link_revisions (link_id, id DESC);
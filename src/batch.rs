@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CacheOp;
+use crate::error::DbError;
+use crate::models::{CreateLinkRequest, Link, LinkResponse, UpdateLinkRequest};
+
+/// The most operations a single batch may carry.
+pub const MAX_OPERATIONS: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+/// One change in a batch. A `version`, when given, must match the link's current version,
+/// like an `If-Match` header.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Create(CreateLinkRequest),
+    Update {
+        id: i32,
+        version: Option<i32>,
+        #[serde(flatten)]
+        changes: UpdateLinkRequest,
+    },
+    Delete {
+        id: i32,
+        version: Option<i32>,
+    },
+}
+
+impl BatchOperation {
+    /// Rewrite the host the operation sets, e.g. to its canonical form.
+    pub fn map_host(&mut self, f: impl Fn(&str) -> String) {
+        match self {
+            BatchOperation::Create(request) => request.host = f(&request.host),
+            BatchOperation::Update { changes, .. } => changes.host = changes.host.as_deref().map(f),
+            BatchOperation::Delete { .. } => {}
+        }
+    }
+}
+
+/// A link before and after one operation: a create has nothing before, a delete nothing after.
+#[derive(Debug, Clone)]
+pub struct LinkChange {
+    pub before: Option<Link>,
+    pub after: Option<Link>,
+}
+
+impl LinkChange {
+    /// The cache updates that mirror this change.
+    pub fn into_cache_ops(self) -> impl Iterator<Item = CacheOp> {
+        let removed = self.before.as_ref().map(CacheOp::remove);
+        removed.into_iter().chain(self.after.map(CacheOp::Insert))
+    }
+}
+
+/// Errors that belong to a single operation, reported alongside the others. Anything else,
+/// such as losing the database, abandons the whole batch.
+pub fn is_operation_error(error: &DbError) -> bool {
+    matches!(error, DbError::NotFound | DbError::Conflict { .. } | DbError::Stale)
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    /// The batch is all or nothing: false means nothing was written
+    pub applied: bool,
+    pub results: Vec<BatchResult>,
+}
+
+impl BatchReport {
+    pub fn new(results: Vec<BatchResult>) -> Self {
+        Self {
+            applied: results.iter().all(|result| result.ok),
+            results,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub index: usize,
+    pub ok: bool,
+    /// The link as written, or as it was before being deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    pub fn ok(index: usize, change: &LinkChange) -> Self {
+        Self {
+            index,
+            ok: true,
+            link: change.after.as_ref().or(change.before.as_ref()).cloned().map(LinkResponse::from),
+            error: None,
+        }
+    }
+
+    pub fn failed(index: usize, error: &DbError) -> Self {
+        Self {
+            index,
+            ok: false,
+            link: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_operations() {
+        let request: BatchRequest = serde_json::from_str(
+            r#"{"operations": [
+                {"op": "create", "host": "go", "source": "/a", "target": "https://a.example.com"},
+                {"op": "update", "id": 2, "version": 3, "target": "https://b.example.com"},
+                {"op": "delete", "id": 4}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(&request.operations[0], BatchOperation::Create(link) if link.source == "/a"));
+        match &request.operations[1] {
+            BatchOperation::Update { id, version, changes } => {
+                assert_eq!((*id, *version), (2, Some(3)));
+                assert_eq!(changes.target.as_deref(), Some("https://b.example.com"));
+                assert!(changes.host.is_none());
+            }
+            other => panic!("expected an update, got {:?}", other),
+        }
+        assert!(matches!(request.operations[2], BatchOperation::Delete { id: 4, version: None }));
+
+        assert!(serde_json::from_str::<BatchRequest>(r#"{"operations": [{"op": "rename", "id": 1}]}"#).is_err());
+    }
+}
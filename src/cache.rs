@@ -0,0 +1,483 @@
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::{
+    database::{Database, MemoryStore},
+    error::DbResult,
+    models::Link,
+};
+
+/// Links keyed by `(host, source)`.
+pub type LinkMap = HashMap<(String, String), Link>;
+
+/// A single change to the cache.
+#[derive(Debug, Clone)]
+pub enum CacheOp {
+    Insert(Link),
+    Remove { host: String, source: String },
+}
+
+impl CacheOp {
+    pub fn remove(link: &Link) -> Self {
+        CacheOp::Remove {
+            host: link.host.clone(),
+            source: link.source.clone(),
+        }
+    }
+}
+
+enum Write {
+    Apply(Vec<CacheOp>),
+    /// Start journaling applied ops for a reload that is about to read the database
+    BeginReload,
+    /// Swap in links read since the `BeginReload` acknowledged as `since`, replaying the
+    /// ops applied after it so changes made during the read aren't lost
+    FinishReload { links: LinkMap, since: u64 },
+    /// A reload that began but failed to read the database
+    AbandonReload,
+}
+
+struct WriteRequest {
+    write: Write,
+    done: oneshot::Sender<Written>,
+}
+
+/// The writer's acknowledgement: how many batches of ops it had applied, and the drift a
+/// reload found.
+#[derive(Debug, Default)]
+struct Written {
+    seq: u64,
+    drift: CacheDrift,
+}
+
+/// The redirect cache: an immutable snapshot behind an atomic pointer.
+///
+/// Readers grab the current snapshot without locking and keep using it for as long as they
+/// like. All mutations go through a single writer task, which copies the map, applies every
+/// pending change in one batch and swaps the new snapshot in, so a bulk edit costs one copy
+/// and never blocks a redirect.
+#[derive(Clone)]
+pub struct LinkCache {
+    snapshot: Arc<ArcSwap<LinkMap>>,
+    writer: mpsc::UnboundedSender<WriteRequest>,
+    store: Arc<Mutex<Option<SnapshotStore>>>,
+}
+
+/// The store [`LinkCache::store`] last built, and the snapshot it was built from.
+type SnapshotStore = (Arc<LinkMap>, Database);
+
+impl LinkCache {
+    /// Create a cache holding `links` and spawn its writer task on the current runtime.
+    pub fn new(links: LinkMap) -> Self {
+        let snapshot = Arc::new(ArcSwap::from_pointee(links));
+        let (writer, requests) = mpsc::unbounded_channel();
+        tokio::spawn(run_writer(snapshot.clone(), requests));
+        Self {
+            snapshot,
+            writer,
+            store: Default::default(),
+        }
+    }
+
+    /// The current contents of the cache.
+    pub fn snapshot(&self) -> Arc<LinkMap> {
+        self.snapshot.load_full()
+    }
+
+    /// A store holding the current snapshot, for serving reads without the database. It's
+    /// built once per snapshot and shared until the cache changes.
+    pub fn store(&self) -> Database {
+        let snapshot = self.snapshot();
+        let mut built = self.store.lock().unwrap();
+        match &*built {
+            Some((links, store)) if Arc::ptr_eq(links, &snapshot) => store.clone(),
+            _ => {
+                let store = Database::read_only(MemoryStore::from_links(snapshot.values().cloned()));
+                *built = Some((snapshot, store.clone()));
+                store
+            }
+        }
+    }
+
+    /// Drop the store built by [`LinkCache::store`] once it's no longer needed.
+    pub fn release_store(&self) {
+        self.store.lock().unwrap().take();
+    }
+
+    /// Apply `ops` atomically; returns once readers can see the result.
+    pub async fn apply(&self, ops: Vec<CacheOp>) {
+        self.write(Write::Apply(ops)).await;
+    }
+
+    pub async fn insert(&self, link: Link) {
+        self.apply(vec![CacheOp::Insert(link)]).await;
+    }
+
+    pub async fn remove(&self, link: &Link) {
+        self.apply(vec![CacheOp::remove(link)]).await;
+    }
+
+    /// Start replacing the cache with a fresh read of the database. Call this before the
+    /// read starts, and hand the links to [`Reload::finish`].
+    pub async fn begin_reload(&self) -> Reload {
+        let since = self.write(Write::BeginReload).await.seq;
+        Reload {
+            writer: self.writer.clone(),
+            since,
+            finished: false,
+        }
+    }
+
+    async fn write(&self, write: Write) -> Written {
+        let (done, applied) = oneshot::channel();
+        if self.writer.send(WriteRequest { write, done }).is_err() {
+            tracing::warn!("Link cache writer has stopped; dropping cache update");
+            return Written::default();
+        }
+        applied.await.unwrap_or_default()
+    }
+}
+
+/// A reload in progress. Dropping it unfinished, e.g. because the database read failed,
+/// stops the writer journaling on its behalf.
+pub struct Reload {
+    writer: mpsc::UnboundedSender<WriteRequest>,
+    since: u64,
+    finished: bool,
+}
+
+impl Reload {
+    /// Swap in `links`, returning how far the cache had drifted from them.
+    pub async fn finish(mut self, links: LinkMap) -> CacheDrift {
+        self.finished = true;
+        let (done, applied) = oneshot::channel();
+        let write = Write::FinishReload { links, since: self.since };
+        if self.writer.send(WriteRequest { write, done }).is_err() {
+            tracing::warn!("Link cache writer has stopped; dropping cache reload");
+            return CacheDrift::default();
+        }
+        applied.await.unwrap_or_default().drift
+    }
+}
+
+impl Drop for Reload {
+    fn drop(&mut self) {
+        if !self.finished {
+            let (done, _) = oneshot::channel();
+            let _ = self.writer.send(WriteRequest { write: Write::AbandonReload, done });
+        }
+    }
+}
+
+fn apply_ops(links: &mut LinkMap, ops: &[CacheOp]) {
+    for op in ops {
+        match op {
+            CacheOp::Insert(link) => {
+                links.insert((link.host.clone(), link.source.clone()), link.clone());
+            }
+            CacheOp::Remove { host, source } => {
+                links.remove(&(host.clone(), source.clone()));
+            }
+        }
+    }
+}
+
+async fn run_writer(snapshot: Arc<ArcSwap<LinkMap>>, mut requests: mpsc::UnboundedReceiver<WriteRequest>) {
+    // Every batch of ops applied, numbered, and kept while a reload is reading the database
+    let mut seq = 0u64;
+    let mut journal: Vec<(u64, Vec<CacheOp>)> = Vec::new();
+    let mut reloads = 0usize;
+
+    while let Some(first) = requests.recv().await {
+        let mut batch = vec![first];
+        while let Ok(next) = requests.try_recv() {
+            batch.push(next);
+        }
+
+        let mut links = LinkMap::clone(&snapshot.load());
+        let mut acks = Vec::with_capacity(batch.len());
+        for request in batch {
+            let drift = match request.write {
+                Write::Apply(ops) => {
+                    apply_ops(&mut links, &ops);
+                    seq += 1;
+                    if reloads > 0 {
+                        journal.push((seq, ops));
+                    }
+                    CacheDrift::default()
+                }
+                Write::BeginReload => {
+                    reloads += 1;
+                    CacheDrift::default()
+                }
+                Write::FinishReload { links: mut fresh, since } => {
+                    for (_, ops) in journal.iter().filter(|(applied, _)| *applied > since) {
+                        apply_ops(&mut fresh, ops);
+                    }
+                    let drift = compute_drift(&links, &fresh);
+                    links = fresh;
+                    reloads -= 1;
+                    drift
+                }
+                Write::AbandonReload => {
+                    reloads -= 1;
+                    CacheDrift::default()
+                }
+            };
+            if reloads == 0 {
+                journal.clear();
+            }
+            acks.push((request.done, Written { seq, drift }));
+        }
+
+        snapshot.store(Arc::new(links));
+        for (done, written) in acks {
+            let _ = done.send(written);
+        }
+    }
+}
+
+/// Differences found between the database and the cache when it was last reloaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheDrift {
+    /// In the database but not the cache
+    pub missing: usize,
+    /// In both, but the cached copy has a different id or target
+    pub stale: usize,
+    /// In the cache but no longer in the database
+    pub extra: usize,
+}
+
+impl CacheDrift {
+    pub fn is_empty(&self) -> bool {
+        self.missing == 0 && self.stale == 0 && self.extra == 0
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheStatus {
+    pub last_reload: Option<DateTime<Utc>>,
+    pub reloads: u64,
+    pub last_drift: Option<CacheDrift>,
+}
+
+pub type CacheStatusHandle = Arc<RwLock<CacheStatus>>;
+
+pub fn compute_drift(cached: &LinkMap, fresh: &LinkMap) -> CacheDrift {
+    let mut drift = CacheDrift::default();
+    for (key, link) in fresh {
+        match cached.get(key) {
+            None => drift.missing += 1,
+            Some(cached) if cached.id != link.id || cached.target != link.target => drift.stale += 1,
+            Some(_) => {}
+        }
+    }
+    drift.extra = cached.keys().filter(|key| !fresh.contains_key(*key)).count();
+    drift
+}
+
+/// Replace the cache with a fresh copy of the database, recording any drift found.
+pub async fn reload(db: &Database, cache: &LinkCache, status: &CacheStatusHandle) -> DbResult<CacheDrift> {
+    let reload = cache.begin_reload().await;
+    let fresh = db
+        .get_all_links()
+        .await?
+        .into_iter()
+        .map(|link| ((link.host.clone(), link.source.clone()), link))
+        .collect();
+    let drift = reload.finish(fresh).await;
+
+    if !drift.is_empty() {
+        tracing::warn!(
+            "Link cache drifted from database: {} missing, {} stale, {} extra",
+            drift.missing, drift.stale, drift.extra
+        );
+    }
+
+    let mut status = status.write().await;
+    status.last_reload = Some(Utc::now());
+    status.reloads += 1;
+    status.last_drift = Some(drift);
+
+    Ok(drift)
+}
+
+/// Periodically reload the cache to pick up edits made directly in SQL or missed notifications.
+pub fn spawn_reconciler(db: Database, cache: LinkCache, status: CacheStatusHandle, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick fires immediately, and the cache was only just loaded
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = reload(&db, &cache, &status).await {
+                tracing::warn!("Failed to reconcile link cache: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DbError;
+
+    fn entry(id: i32, source: &str, target: &str) -> ((String, String), Link) {
+        let link = Link {
+            id,
+            host: "go".to_string(),
+            source: source.to_string(),
+            target: target.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            hit_count: 0,
+            version: 1,
+        };
+        (("go".to_string(), source.to_string()), link)
+    }
+
+    #[test]
+    fn test_compute_drift() {
+        let cached: HashMap<_, _> = [
+            entry(1, "/same", "https://same.example.com"),
+            entry(2, "/stale", "https://old.example.com"),
+            entry(3, "/extra", "https://extra.example.com"),
+        ]
+        .into_iter()
+        .collect();
+        let fresh: HashMap<_, _> = [
+            entry(1, "/same", "https://same.example.com"),
+            entry(2, "/stale", "https://new.example.com"),
+            entry(4, "/missing", "https://missing.example.com"),
+        ]
+        .into_iter()
+        .collect();
+
+        let drift = compute_drift(&cached, &fresh);
+        assert_eq!(drift, CacheDrift { missing: 1, stale: 1, extra: 1 });
+        assert!(compute_drift(&fresh, &fresh).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_changes_made_during_the_read() {
+        let cache = LinkCache::new([entry(1, "/old", "https://old.example.com")].into_iter().collect());
+
+        // The database read misses /new, which was created while it ran
+        let reload = cache.begin_reload().await;
+        let (_, new) = entry(2, "/new", "https://new.example.com");
+        cache.insert(new).await;
+        let fresh: LinkMap = [entry(1, "/old", "https://old.example.com")].into_iter().collect();
+        let drift = reload.finish(fresh).await;
+
+        assert!(drift.is_empty());
+        assert!(cache.snapshot().contains_key(&("go".to_string(), "/new".to_string())));
+
+        // An abandoned reload changes nothing
+        let reload = cache.begin_reload().await;
+        drop(reload);
+        cache.remove(&entry(2, "/new", "https://new.example.com").1).await;
+        assert_eq!(cache.snapshot().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_store_is_built_once_per_snapshot() {
+        let cache = LinkCache::new([entry(1, "/docs", "https://docs.example.com")].into_iter().collect());
+        assert_eq!(cache.store().count_links(None).await.unwrap(), 1);
+
+        // The same store is handed out until the cache changes
+        let store = cache.store();
+        assert!(store.same_store(&cache.store()));
+
+        cache.insert(entry(2, "/wiki", "https://wiki.example.com").1).await;
+        let rebuilt = cache.store();
+        assert!(!store.same_store(&rebuilt));
+        assert_eq!(rebuilt.count_links(None).await.unwrap(), 2);
+        assert!(rebuilt.get_link_by_host_and_source("go", "/wiki").await.unwrap().is_some());
+
+        // It's a read-only view, so changes are refused
+        let request = crate::models::CreateLinkRequest {
+            host: "go".to_string(),
+            source: "/scratch".to_string(),
+            target: "https://scratch.example.com".to_string(),
+        };
+        assert!(matches!(rebuilt.create_link(&request).await, Err(DbError::Unavailable(_))));
+        assert!(matches!(rebuilt.delete_link(1, None).await, Err(DbError::Unavailable(_))));
+        assert_eq!(rebuilt.count_links(None).await.unwrap(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_readers_never_see_partial_batches() {
+        let cache = LinkCache::new(LinkMap::new());
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        // Every batch removes a writer's previous pair of links and adds a new pair, so a
+        // snapshot holding one half of a pair without the other would be a torn write
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                let done = done.clone();
+                tokio::spawn(async move {
+                    let mut reads = 0u64;
+                    while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                        let snapshot = cache.snapshot();
+                        assert!(snapshot.len() <= 8, "stale pairs left behind: {:?}", snapshot.keys());
+                        for (host, source) in snapshot.keys() {
+                            if let Some(n) = source.strip_prefix("/a") {
+                                assert!(snapshot.contains_key(&(host.clone(), format!("/b{}", n))));
+                            }
+                        }
+                        assert_eq!(snapshot.len() % 2, 0);
+                        reads += 1;
+                        tokio::task::yield_now().await;
+                    }
+                    reads
+                })
+            })
+            .collect();
+
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let cache = cache.clone();
+                tokio::spawn(async move {
+                    let mut previous: Option<(Link, Link)> = None;
+                    for i in 0..500 {
+                        let n = w * 1000 + i;
+                        let a = entry(n, &format!("/a{}", n), "https://a.example.com").1;
+                        let b = entry(n, &format!("/b{}", n), "https://b.example.com").1;
+                        let mut ops = Vec::new();
+                        if let Some((old_a, old_b)) = &previous {
+                            ops.push(CacheOp::remove(old_a));
+                            ops.push(CacheOp::remove(old_b));
+                        }
+                        ops.push(CacheOp::Insert(a.clone()));
+                        ops.push(CacheOp::Insert(b.clone()));
+                        cache.apply(ops).await;
+                        previous = Some((a, b));
+                    }
+                })
+            })
+            .collect();
+
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(cache.snapshot().len(), 8);
+        for reader in readers {
+            assert!(reader.await.unwrap() > 0);
+        }
+
+        // Mutations are visible as soon as apply returns
+        let link = entry(99_999, "/final", "https://final.example.com").1;
+        cache.apply(vec![CacheOp::Insert(link.clone())]).await;
+        assert!(cache.snapshot().contains_key(&("go".to_string(), "/final".to_string())));
+        cache.remove(&link).await;
+        assert!(!cache.snapshot().contains_key(&("go".to_string(), "/final".to_string())));
+    }
+}
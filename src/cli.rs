@@ -0,0 +1,514 @@
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, Parser, Subcommand};
+use futures::TryStreamExt;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use axum_server::Handle;
+
+use crate::{
+    cache::{self, CacheStatusHandle},
+    config::Config,
+    create_app, create_metrics_app,
+    database::{Database, ListLinksParams},
+    error::DbError,
+    export,
+    health::{self, Health}, import, listener, load_cache,
+    models::{ConflictPolicy, CreateLinkRequest, ExportFormat, ImportFormat, LinkResponse, LinkSort},
+    redirector, snapshot,
+    sync::Manifest,
+    tls,
+    AppState, LinkCache,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "lynx", version, about = "Link shortener and go-link redirector")]
+pub struct Cli {
+    /// TOML config file; environment variables override its settings
+    #[arg(long, global = true, env = "LYNX_CONFIG")]
+    pub config: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the web server (the default when no command is given)
+    Serve,
+    /// Apply pending database migrations
+    Migrate,
+    /// Manage individual links
+    #[command(subcommand)]
+    Links(LinksCommand),
+    /// Bulk import links from a CSV, JSON or bookmark HTML file
+    Import(ImportArgs),
+    /// Export every link
+    Export(ExportArgs),
+    /// Make the links in a manifest's scope match the manifest
+    Sync(SyncArgs),
+    /// Check configuration, database connectivity and stored links
+    Check,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LinksCommand {
+    /// Create a link
+    Add {
+        source: String,
+        target: String,
+        /// Defaults to DEFAULT_REDIRECT_HOST
+        #[arg(long)]
+        host: Option<String>,
+    },
+    /// List links
+    List {
+        #[arg(long)]
+        host: Option<String>,
+        #[arg(long, value_enum, default_value = "created")]
+        sort: LinkSort,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+    /// Delete a link by id
+    Rm { id: i32 },
+    /// Show where a path redirects to
+    Resolve {
+        path: String,
+        /// Defaults to DEFAULT_REDIRECT_HOST
+        #[arg(long)]
+        host: Option<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    #[arg(long, short)]
+    pub file: PathBuf,
+    /// Inferred from the file extension when omitted
+    #[arg(long, value_enum)]
+    pub format: Option<ImportFormat>,
+    /// Host for entries that don't name one; defaults to DEFAULT_REDIRECT_HOST
+    #[arg(long)]
+    pub host: Option<String>,
+    #[arg(long, value_enum, default_value = "fail")]
+    pub policy: ConflictPolicy,
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[arg(long, value_enum, default_value = "json")]
+    pub format: ExportFormat,
+    /// Write to a file instead of stdout
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct SyncArgs {
+    #[arg(long, short)]
+    pub file: PathBuf,
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+pub async fn run(command: Command, config: Config) -> Result<()> {
+    // The server opens the database itself, as it may start before the database is up
+    if let Command::Serve = command {
+        return serve(config).await;
+    }
+    let db = Database::connect(&config.database_url, &config.pool).await?;
+
+    match command {
+        Command::Serve => unreachable!("the server returned above"),
+        Command::Migrate => {
+            db.migrate().await?;
+            println!("Migrations applied");
+            Ok(())
+        }
+        Command::Links(command) => links(&db, &config, command).await,
+        Command::Import(args) => import_file(&db, &config, args).await,
+        Command::Export(args) => export_links(&db, args).await,
+        Command::Sync(args) => sync(&db, &config, args).await,
+        Command::Check => check(&db, &config).await,
+    }
+}
+
+/// Connect to the database and load the link cache from it. If the database is unreachable
+/// and a cache snapshot was saved, redirect from the snapshot instead, read-only, while the
+/// connection is retried in the background; the database monitor then reloads the cache and
+/// accepts changes again.
+pub async fn open_storage(config: &Config, cache_status: &CacheStatusHandle, health: &Health) -> Result<(Database, LinkCache)> {
+    let snapshot_path = config.snapshot_path.as_deref().filter(|path| path.exists());
+    // With a snapshot to fall back on, one attempt is enough before serving from it
+    let connected = match snapshot_path {
+        Some(_) => Database::connect_once(&config.database_url, &config.pool).await,
+        None => Database::connect(&config.database_url, &config.pool).await,
+    };
+
+    let db = match (connected, snapshot_path) {
+        (Err(DbError::Unavailable(e)), Some(path)) => {
+            tracing::warn!("Database unavailable at startup: {}", e);
+            match snapshot::load(path).await {
+                Ok(saved) => return Ok(serve_snapshot(saved, config, cache_status, health).await),
+                Err(e) => {
+                    tracing::warn!("Ignoring cache snapshot: {:#}", e);
+                    Database::connect(&config.database_url, &config.pool).await?
+                }
+            }
+        }
+        (connected, _) => connected?,
+    };
+
+    prepare_schema(&db, config).await?;
+    let cache = load_cache(&db).await?;
+    cache_status.write().await.last_reload = Some(chrono::Utc::now());
+    if let Some(path) = &config.snapshot_path {
+        if let Err(e) = snapshot::save(path, &cache.snapshot()).await {
+            tracing::warn!("{:#}", e);
+        }
+    }
+    Ok((db, cache))
+}
+
+async fn serve_snapshot(saved: snapshot::Snapshot, config: &Config, cache_status: &CacheStatusHandle, health: &Health) -> (Database, LinkCache) {
+    tracing::info!(
+        "Serving {} links from the cache snapshot saved at {} until the database connects",
+        saved.links.len(),
+        saved.saved_at
+    );
+    let (db, connect) = Database::connect_later(&config.database_url, &config.pool);
+    // The snapshot counts as a load: it's what the cache held when it was saved
+    cache_status.write().await.last_reload = Some(saved.saved_at);
+    let cache = LinkCache::new(saved.into_link_map());
+    health.set_read_only(true);
+    let (connected_db, connected_config) = (db.clone(), config.clone());
+    tokio::spawn(async move {
+        if let Err(e) = connect.await {
+            tracing::error!("Giving up on connecting to the database: {}", e);
+            return;
+        }
+        // Same as a normal start: a schema this binary can't handle stops the server
+        if let Err(e) = prepare_schema(&connected_db, &connected_config).await {
+            tracing::error!("{:#}", e);
+            std::process::exit(1);
+        }
+        tracing::info!("Connected to the database");
+    });
+    (db, cache)
+}
+
+async fn serve(config: Config) -> Result<()> {
+    let cache_status = CacheStatusHandle::default();
+    let health = Health::default();
+    let (db, cache) = open_storage(&config, &cache_status, &health).await?;
+    if let Some(path) = &config.snapshot_path {
+        snapshot::spawn_writer(cache.clone(), path.clone());
+    }
+
+    listener::spawn_cache_listener(config.database_url.clone(), db.clone(), cache.clone(), cache_status.clone());
+    if config.cache_reload_interval > 0 {
+        cache::spawn_reconciler(
+            db.clone(),
+            cache.clone(),
+            cache_status.clone(),
+            Duration::from_secs(config.cache_reload_interval),
+        );
+    }
+
+    let state = AppState {
+        db,
+        cache,
+        cache_status,
+        config: config.clone(),
+        health: health.clone(),
+    };
+    health::spawn_database_monitor(state.clone());
+
+    // Build our application with routes
+    let metrics_app = config.metrics_port.map(|_| create_metrics_app(state.clone()));
+    let app = create_app(state);
+
+    // Peer addresses decide whether forwarded headers are trusted
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    let bind_addr = format!("{}:{}", config.host, config.port);
+    let listener = std::net::TcpListener::bind(&bind_addr)?;
+    listener.set_nonblocking(true)?;
+
+    let rustls = match &config.tls {
+        Some(tls) => Some(tls::load(tls).await?),
+        None => None,
+    };
+
+    println!("Server running on {}://{}", if rustls.is_some() { "https" } else { "http" }, bind_addr);
+    println!("Admin UI: {}", config.admin_url());
+    for site in config.hosts.iter().filter(|h| !h.admin) {
+        println!("Redirector: {}://{}:{}", config.scheme_for(Some(site)), site.name, config.port);
+    }
+
+    // Every listener stops together on shutdown
+    let handle = Handle::new();
+    tokio::spawn(shutdown_on_signal(handle.clone(), health, Duration::from_secs(config.shutdown_timeout)));
+
+    if let (Some(port), Some(metrics_app)) = (config.metrics_port, metrics_app) {
+        let metrics_addr = format!("{}:{}", config.host, port);
+        let metrics_listener = std::net::TcpListener::bind(&metrics_addr)?;
+        metrics_listener.set_nonblocking(true)?;
+        println!("Metrics: http://{}/metrics", metrics_addr);
+
+        let metrics_server = axum_server::from_tcp(metrics_listener).handle(handle.clone());
+        tokio::spawn(async move {
+            if let Err(e) = metrics_server.serve(metrics_app.into_make_service()).await {
+                tracing::error!("Metrics listener failed: {}", e);
+            }
+        });
+    }
+
+    match (rustls, &config.tls) {
+        (Some(rustls), Some(tls)) => {
+            tls::spawn_reload_on_sighup(rustls.clone(), tls.clone());
+
+            if let Some(port) = tls.http_redirect_port {
+                let redirect_addr = format!("{}:{}", config.host, port);
+                let redirect_listener = std::net::TcpListener::bind(&redirect_addr)?;
+                redirect_listener.set_nonblocking(true)?;
+                println!("Redirecting http://{} to HTTPS", redirect_addr);
+
+                let redirect = axum_server::from_tcp(redirect_listener).handle(handle.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = redirect.serve(tls::redirect_app(config.port).into_make_service()).await {
+                        tracing::error!("HTTP redirect listener failed: {}", e);
+                    }
+                });
+            }
+
+            axum_server::from_tcp_rustls(listener, rustls).handle(handle).serve(app).await?;
+        }
+        _ => axum_server::from_tcp(listener).handle(handle).serve(app).await?,
+    }
+
+    println!("Server stopped");
+    Ok(())
+}
+
+/// Migrate if configured to, and refuse to run against a schema from a newer Lynx.
+async fn prepare_schema(db: &Database, config: &Config) -> Result<()> {
+    if config.auto_migrate {
+        db.migrate().await?;
+    }
+
+    let status = db.schema_status().await?;
+    status.ensure_compatible()?;
+    if status.pending > 0 {
+        tracing::warn!(
+            "Pending database migrations: {}; run `lynx migrate` or set AUTO_MIGRATE=true",
+            status.pending
+        );
+    }
+    Ok(())
+}
+
+/// On SIGTERM or Ctrl-C, stop accepting connections and give open ones `drain_timeout`
+/// to finish. Readiness fails from the moment the signal arrives.
+async fn shutdown_on_signal(handle: Handle, health: Health, drain_timeout: Duration) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    tracing::info!("Shutting down, draining connections for up to {}s", drain_timeout.as_secs());
+    health.start_draining();
+    handle.graceful_shutdown(Some(drain_timeout));
+}
+
+async fn links(db: &Database, config: &Config, command: LinksCommand) -> Result<()> {
+    match command {
+        LinksCommand::Add { source, target, host } => {
+            let request = CreateLinkRequest {
+                host: host.map_or_else(|| config.default_redirect_host.clone(), |host| config.canonical_host(&host)),
+                source,
+                target,
+            };
+            let link = db.create_link(&request).await?;
+            println!("Created link {}: {}{} -> {}", link.id, link.host, link.source, link.target);
+        }
+        LinksCommand::List { host, sort, limit } => {
+            let params = ListLinksParams {
+                host,
+                sort,
+                order: sort.default_order(),
+                cursor: None,
+                limit: limit.max(1),
+            };
+            let page = db.list_links(&params).await?;
+            for link in &page.links {
+                println!("{}\t{}\t{}\t{}\t{}", link.id, link.host, link.source, link.target, link.hit_count);
+            }
+            eprintln!("Showing {} of {} links", page.links.len(), page.total);
+        }
+        LinksCommand::Rm { id } => {
+            match db.delete_link(id, None).await {
+                Err(DbError::NotFound) => bail!("Link {} not found", id),
+                result => result?,
+            };
+            println!("Deleted link {}", id);
+        }
+        LinksCommand::Resolve { path, host } => {
+            let host = host.map_or_else(|| config.default_redirect_host.clone(), |host| config.canonical_host(&host));
+            let cache = load_cache(db).await?.snapshot();
+            match redirector::resolve(&cache, &host, &path) {
+                Some(resolution) => {
+                    let link = resolution.link;
+                    println!(
+                        "{} (via link {}: {}{}, {} match)",
+                        resolution.target, link.id, link.host, link.source, resolution.kind.as_str()
+                    );
+                }
+                None => bail!("No link matches {}{}", host, path),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn import_file(db: &Database, config: &Config, args: ImportArgs) -> Result<()> {
+    let format = match args.format {
+        Some(format) => format,
+        None => format_from_extension(&args.file)?,
+    };
+    let host = args.host.unwrap_or_else(|| config.default_redirect_host.clone());
+    let links = import::parse_links(format, &std::fs::read_to_string(&args.file)?, &host)?;
+    let links = config.canonical_links(links);
+
+    let report = if args.dry_run {
+        db.preview_import(&links, args.policy).await?
+    } else {
+        db.import_links(&links, args.policy).await?.0
+    };
+
+    for conflict in &report.conflicts {
+        println!("! {}{} -> {} ({})", conflict.host, conflict.source, conflict.target, conflict.reason);
+    }
+    println!(
+        "{} links: {} created, {} updated, {} skipped",
+        report.total, report.created, report.updated, report.skipped
+    );
+
+    if args.dry_run {
+        println!("Dry run, no changes applied");
+    } else if !report.applied {
+        bail!("Import aborted: a link conflicts with an existing one");
+    }
+
+    Ok(())
+}
+
+fn format_from_extension(path: &Path) -> Result<ImportFormat> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "csv" => Ok(ImportFormat::Csv),
+        "json" => Ok(ImportFormat::Json),
+        "html" | "htm" => Ok(ImportFormat::Bookmarks),
+        _ => Err(anyhow!("Cannot tell the format of {}; pass --format", path.display())),
+    }
+}
+
+async fn export_links(db: &Database, args: ExportArgs) -> Result<()> {
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut out = std::io::BufWriter::new(&mut out);
+
+    out.write_all(export::header(args.format).as_bytes())?;
+    let mut links = db.stream_links();
+    let mut empty = true;
+    while let Some(link) = links.try_next().await? {
+        out.write_all(export::record(args.format, &LinkResponse::from(link), empty)?.as_bytes())?;
+        empty = false;
+    }
+    out.write_all(export::footer(args.format, empty).as_bytes())?;
+    out.flush()?;
+
+    Ok(())
+}
+
+async fn sync(db: &Database, config: &Config, args: SyncArgs) -> Result<()> {
+    let mut manifest = Manifest::parse(&std::fs::read_to_string(&args.file)?)?;
+    manifest.map_hosts(|host| config.canonical_host(host));
+    let desired = manifest.desired_links()?;
+    let (plan, _) = db.sync_links(&manifest.scope, &desired, !args.dry_run).await?;
+
+    println!("{}", plan);
+    if plan.is_empty() {
+        println!("Nothing to do");
+    } else if args.dry_run {
+        println!("Dry run, no changes applied");
+    } else {
+        println!("Applied");
+    }
+
+    Ok(())
+}
+
+async fn check(db: &Database, config: &Config) -> Result<()> {
+    println!("Admin host: {}", config.admin_host);
+    println!("Default redirect host: {}", config.default_redirect_host);
+    for site in &config.hosts {
+        let role = if site.admin { "admin" } else { "redirect" };
+        println!("Host {} ({}): aliases [{}], on miss {:?}", site.name, role, site.aliases.join(", "), site.on_miss);
+    }
+
+    let links = db.get_all_links().await?;
+    println!("Database: connected, {} links", links.len());
+
+    let mut problems = 0;
+    for link in &links {
+        if !link.source.starts_with('/') {
+            println!("! link {} ({}{}): source should start with '/'", link.id, link.host, link.source);
+            problems += 1;
+        }
+        if link.host != config.canonical_host(&link.host) {
+            println!("! link {} ({}{}): host should be '{}'", link.id, link.host, link.source, config.canonical_host(&link.host));
+            problems += 1;
+        }
+        if !link.target.starts_with("http://") && !link.target.starts_with("https://") {
+            println!("! link {} ({}{}): target '{}' is not an http(s) URL", link.id, link.host, link.source, link.target);
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        bail!("{} problem(s) found", problems);
+    }
+    println!("OK");
+
+    Ok(())
+}
@@ -1,35 +1,679 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use serde::Deserialize;
 use std::env;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::models::CreateLinkRequest;
 
 #[derive(Debug, Clone)]
 pub struct Config {
+    /// Host serving the admin UI, used when linking to it from other hosts
     pub admin_host: String,
+    /// Host given to links created without one
     pub default_redirect_host: String,
+    pub hosts: Vec<HostConfig>,
     pub database_url: String,
+    pub pool: PoolConfig,
+    /// Apply pending migrations when the server starts
+    pub auto_migrate: bool,
+    /// Cache snapshot to start redirecting from while the database is unreachable
+    pub snapshot_path: Option<PathBuf>,
     pub port: u16,
     pub host: String,
+    /// Seconds between full cache reloads from the database; 0 disables them
+    pub cache_reload_interval: u64,
+    /// Base URL users reach the admin UI at, e.g. `https://lynx.example.com`
+    pub public_url: Option<String>,
+    /// Peers whose `X-Forwarded-Proto` and `X-Forwarded-Host` headers are believed
+    pub trusted_proxies: Vec<IpNet>,
+    /// Serve HTTPS directly instead of plain HTTP
+    pub tls: Option<TlsConfig>,
+    /// Seconds open connections get to finish after SIGTERM before they're dropped
+    pub shutdown_timeout: u64,
+    /// Serve `/metrics` on this port instead of on the admin host
+    pub metrics_port: Option<u16>,
+    pub log_format: LogFormat,
+    /// OTLP/HTTP collector to export spans to, e.g. `http://localhost:4318`
+    pub otlp_endpoint: Option<String>,
+}
+
+/// How log lines are written to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregators
+    Json,
+}
+
+/// Database connection pool settings.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolConfig {
+    /// Most connections open at once
+    pub max_connections: u32,
+    /// Seconds a query waits for a free connection before failing
+    pub acquire_timeout: u64,
+    /// Seconds an unused connection stays open; 0 keeps them open
+    pub idle_timeout: u64,
+    /// Milliseconds a Postgres statement may run before it's cancelled; 0 for no limit
+    pub statement_timeout: u64,
+    /// Times to retry the first connection at startup, waiting twice as long each time
+    pub connect_retries: u32,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            acquire_timeout: 30,
+            idle_timeout: 600,
+            statement_timeout: 0,
+            connect_retries: 5,
+        }
+    }
+}
+
+/// Certificate and key for serving HTTPS, reloaded from disk on SIGHUP.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert: PathBuf,
+    /// PEM private key
+    pub key: PathBuf,
+    /// Also listen for plain HTTP on this port, only to redirect to HTTPS
+    pub http_redirect_port: Option<u16>,
+}
+
+/// How one host name is served.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostConfig {
+    pub name: String,
+    /// Other names this host is reached by
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Serve the admin UI and API instead of redirecting
+    #[serde(default)]
+    pub admin: bool,
+    /// Give links created without a host this one
+    #[serde(default)]
+    pub default: bool,
+    #[serde(default)]
+    pub on_miss: MissBehaviour,
+    /// Scheme for link targets that don't name one, and for links to this host
+    #[serde(default = "default_scheme")]
+    pub default_scheme: String,
+}
+
+/// What a redirect host does with a path no link matches.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissBehaviour {
+    /// Send the user to the admin UI to create the link
+    #[default]
+    AddLink,
+    NotFound,
+    /// Redirect to this URL, with `{path}` replaced by the requested path
+    Redirect(String),
+}
+
+fn default_scheme() -> String {
+    "http".to_string()
+}
+
+impl HostConfig {
+    pub fn new(name: &str, admin: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            aliases: Vec::new(),
+            admin,
+            default: false,
+            on_miss: MissBehaviour::default(),
+            default_scheme: default_scheme(),
+        }
+    }
+
+    /// Whether the normalized `host` is this host's name or one of its aliases.
+    pub fn matches(&self, host: &str) -> bool {
+        self.name == host || self.aliases.iter().any(|alias| alias == host)
+    }
+
+    fn normalize(mut self) -> Self {
+        self.name = normalize_host(&self.name);
+        self.aliases = self.aliases.iter().map(|alias| normalize_host(alias)).collect();
+        self
+    }
+}
+
+/// Lowercase `host`, drop any port and convert internationalized names to punycode, so
+/// `go`, `GO:3100` and `go.` all compare equal.
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+
+    // IPv6 literals keep their brackets but lose the port
+    if let Some(rest) = host.strip_prefix('[') {
+        let address = rest.split_once(']').map_or(rest, |(address, _)| address);
+        return format!("[{}]", address.to_ascii_lowercase());
+    }
+
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    let name = name.trim_end_matches('.');
+
+    idna::domain_to_ascii(name).unwrap_or_else(|_| name.to_lowercase())
+}
+
+/// A proxy given as a network (`10.0.0.0/8`) or a single address.
+fn parse_proxy(proxy: &str) -> Result<IpNet> {
+    proxy
+        .parse::<IpNet>()
+        .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| anyhow::anyhow!("TRUSTED_PROXIES entry '{}' is not an address or network", proxy))
+}
+
+/// `name` from the environment parsed as a number, if set.
+fn parse_env<T: std::str::FromStr>(var: impl Fn(&str) -> Option<String>, name: &str, expected: &str) -> Result<Option<T>> {
+    match var(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("{} must be {}", name, expected)),
+        None => Ok(None),
+    }
+}
+
+/// Settings read from the TOML config file; anything left out falls back to the environment.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    database_url: Option<String>,
+    #[serde(default)]
+    pool: PoolConfig,
+    auto_migrate: Option<bool>,
+    snapshot_path: Option<PathBuf>,
+    port: Option<u16>,
+    host: Option<String>,
+    cache_reload_interval: Option<u64>,
+    shutdown_timeout: Option<u64>,
+    metrics_port: Option<u16>,
+    log_format: Option<LogFormat>,
+    otlp_endpoint: Option<String>,
+    public_url: Option<String>,
+    #[serde(default)]
+    trusted_proxies: Vec<IpNet>,
+    tls: Option<TlsConfig>,
+    #[serde(default)]
+    hosts: Vec<HostConfig>,
 }
 
 impl Config {
+    /// Load from the file named by `LYNX_CONFIG`, if any, with environment variables overriding it.
     pub fn from_env() -> Result<Self> {
-        let admin_host = env::var("ADMIN_HOST").unwrap_or_else(|_| "lynx".to_string());
-        let default_redirect_host = env::var("DEFAULT_REDIRECT_HOST").unwrap_or_else(|_| "go".to_string());
-        let database_url = env::var("DATABASE_URL")
-            .map_err(|_| anyhow::anyhow!("DATABASE_URL environment variable is required"))?;
-        
-        let port = env::var("PORT")
-            .unwrap_or_else(|_| "3100".to_string())
-            .parse::<u16>()
-            .map_err(|_| anyhow::anyhow!("PORT must be a valid number"))?;
-        
-        let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
+        match env::var("LYNX_CONFIG") {
+            Ok(path) => Self::load(Some(Path::new(&path))),
+            Err(_) => Self::load(None),
+        }
+    }
+
+    /// Load from a TOML config file, with environment variables overriding it.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let file = match path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read config file {}", path.display()))?;
+                toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))?
+            }
+            None => FileConfig::default(),
+        };
+
+        Self::from_file(file)
+    }
+
+    fn from_file(file: FileConfig) -> Result<Self> {
+        Self::resolve(file, |name| env::var(name).ok())
+    }
+
+    /// Combine the file's settings with environment variables looked up through `var`.
+    fn resolve(file: FileConfig, var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let database_url = var("DATABASE_URL")
+            .or(file.database_url)
+            .ok_or_else(|| anyhow::anyhow!("DATABASE_URL environment variable is required"))?;
+
+        let mut pool = file.pool;
+        if let Some(max) = parse_env(&var, "DB_MAX_CONNECTIONS", "a number of connections")? {
+            pool.max_connections = max;
+        }
+        if let Some(timeout) = parse_env(&var, "DB_ACQUIRE_TIMEOUT", "a number of seconds")? {
+            pool.acquire_timeout = timeout;
+        }
+        if let Some(timeout) = parse_env(&var, "DB_IDLE_TIMEOUT", "a number of seconds")? {
+            pool.idle_timeout = timeout;
+        }
+        if let Some(timeout) = parse_env(&var, "DB_STATEMENT_TIMEOUT", "a number of milliseconds")? {
+            pool.statement_timeout = timeout;
+        }
+        if let Some(retries) = parse_env(&var, "DB_CONNECT_RETRIES", "a number")? {
+            pool.connect_retries = retries;
+        }
+        if pool.max_connections == 0 {
+            anyhow::bail!("DB_MAX_CONNECTIONS must be at least 1");
+        }
+
+        let auto_migrate = match var("AUTO_MIGRATE").as_deref() {
+            Some("true" | "1") => true,
+            Some("false" | "0") => false,
+            Some(other) => anyhow::bail!("AUTO_MIGRATE must be 'true' or 'false', got '{}'", other),
+            None => file.auto_migrate.unwrap_or(false),
+        };
+
+        let snapshot_path = var("SNAPSHOT_PATH")
+            .map(PathBuf::from)
+            .or(file.snapshot_path)
+            .filter(|path| !path.as_os_str().is_empty());
+
+        let port = match var("PORT") {
+            Some(port) => port.parse::<u16>().map_err(|_| anyhow::anyhow!("PORT must be a valid number"))?,
+            None => file.port.unwrap_or(3100),
+        };
+
+        let host = var("HOST").or(file.host).unwrap_or_else(|| "0.0.0.0".to_string());
+
+        let cache_reload_interval = match var("CACHE_RELOAD_INTERVAL") {
+            Some(interval) => interval
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("CACHE_RELOAD_INTERVAL must be a number of seconds"))?,
+            None => file.cache_reload_interval.unwrap_or(300),
+        };
+
+        let shutdown_timeout = match var("SHUTDOWN_TIMEOUT") {
+            Some(timeout) => timeout
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("SHUTDOWN_TIMEOUT must be a number of seconds"))?,
+            None => file.shutdown_timeout.unwrap_or(30),
+        };
+
+        let metrics_port = match var("METRICS_PORT") {
+            Some(port) => Some(port.parse::<u16>().map_err(|_| anyhow::anyhow!("METRICS_PORT must be a valid number"))?),
+            None => file.metrics_port,
+        };
+
+        let log_format = match var("LOG_FORMAT").as_deref() {
+            Some("json") => LogFormat::Json,
+            Some("text") => LogFormat::Text,
+            Some(other) => anyhow::bail!("LOG_FORMAT must be 'text' or 'json', got '{}'", other),
+            None => file.log_format.unwrap_or_default(),
+        };
+
+        let otlp_endpoint = var("OTLP_ENDPOINT")
+            .or(file.otlp_endpoint)
+            .filter(|url| !url.is_empty());
+        if let Some(url) = &otlp_endpoint {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                anyhow::bail!("OTLP_ENDPOINT must be an http(s) URL, got '{}'", url);
+            }
+        }
+
+        let public_url = var("PUBLIC_URL")
+            .or(file.public_url)
+            .filter(|url| !url.is_empty())
+            .map(|url| url.trim_end_matches('/').to_string());
+        if let Some(url) = &public_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                anyhow::bail!("PUBLIC_URL must be an http(s) URL, got '{}'", url);
+            }
+        }
+
+        let trusted_proxies = match var("TRUSTED_PROXIES") {
+            Some(proxies) => proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(parse_proxy)
+                .collect::<Result<_>>()?,
+            None => file.trusted_proxies,
+        };
+
+        let mut tls = match (var("TLS_CERT"), var("TLS_KEY")) {
+            (Some(cert), Some(key)) => Some(TlsConfig {
+                cert: cert.into(),
+                key: key.into(),
+                http_redirect_port: file.tls.and_then(|tls| tls.http_redirect_port),
+            }),
+            (None, None) => file.tls,
+            _ => anyhow::bail!("TLS_CERT and TLS_KEY must be set together"),
+        };
+        if let Some(port) = var("HTTP_REDIRECT_PORT") {
+            let port = port
+                .parse::<u16>()
+                .map_err(|_| anyhow::anyhow!("HTTP_REDIRECT_PORT must be a valid number"))?;
+            match tls.as_mut() {
+                Some(tls) => tls.http_redirect_port = Some(port),
+                None => anyhow::bail!("HTTP_REDIRECT_PORT needs TLS_CERT and TLS_KEY"),
+            }
+        }
+
+        let mut hosts: Vec<HostConfig> = file.hosts.into_iter().map(HostConfig::normalize).collect();
+        let admin_host = var("ADMIN_HOST")
+            .map(|host| normalize_host(&host))
+            .or_else(|| hosts.iter().find(|h| h.admin).map(|h| h.name.clone()))
+            .unwrap_or_else(|| "lynx".to_string());
+        let default_redirect_host = var("DEFAULT_REDIRECT_HOST")
+            .map(|host| normalize_host(&host))
+            .or_else(|| hosts.iter().find(|h| h.default).map(|h| h.name.clone()))
+            .or_else(|| hosts.iter().find(|h| !h.admin).map(|h| h.name.clone()))
+            .unwrap_or_else(|| "go".to_string());
+
+        // Hosts named only through the environment get the defaults
+        if !hosts.iter().any(|h| h.matches(&admin_host)) {
+            hosts.push(HostConfig::new(&admin_host, true));
+        }
+        if !hosts.iter().any(|h| h.matches(&default_redirect_host)) {
+            hosts.push(HostConfig::new(&default_redirect_host, false));
+        }
 
         Ok(Self {
             admin_host,
             default_redirect_host,
+            hosts,
             database_url,
+            pool,
+            auto_migrate,
+            snapshot_path,
             port,
             host,
+            cache_reload_interval,
+            public_url,
+            trusted_proxies,
+            tls,
+            shutdown_timeout,
+            metrics_port,
+            log_format,
+            otlp_endpoint,
         })
     }
+
+    /// The configured host reached as `host`, by name or alias.
+    pub fn host_config(&self, host: &str) -> Option<&HostConfig> {
+        let host = normalize_host(host);
+        self.hosts.iter().find(|h| h.matches(&host))
+    }
+
+    /// The name links for `host` are stored under: normalized, with aliases mapped to
+    /// the host they belong to.
+    pub fn canonical_host(&self, host: &str) -> String {
+        match self.host_config(host) {
+            Some(site) => site.name.clone(),
+            None => normalize_host(host),
+        }
+    }
+
+    /// The settings redirects on `host` follow: its own, or the defaults for a host
+    /// missing from the config.
+    pub fn site(&self, host: &str) -> HostConfig {
+        self.host_config(host)
+            .cloned()
+            .unwrap_or_else(|| HostConfig::new(&normalize_host(host), false))
+    }
+
+    /// Store every link under its canonical host.
+    pub fn canonical_links(&self, links: Vec<CreateLinkRequest>) -> Vec<CreateLinkRequest> {
+        links
+            .into_iter()
+            .map(|link| CreateLinkRequest {
+                host: self.canonical_host(&link.host),
+                ..link
+            })
+            .collect()
+    }
+
+    /// Base URL of the admin UI, as seen from outside when nothing is known about the request.
+    pub fn admin_url(&self) -> String {
+        self.admin_url_for(None)
+    }
+
+    /// Base URL of the admin UI for a request a trusted proxy received over `forwarded_proto`.
+    /// A configured public URL always wins; behind a proxy the public port is the scheme's
+    /// default, not the one we listen on.
+    pub fn admin_url_for(&self, forwarded_proto: Option<&str>) -> String {
+        if let Some(url) = &self.public_url {
+            return url.clone();
+        }
+
+        match forwarded_proto {
+            Some(scheme) => format!("{}://{}", scheme, self.admin_host),
+            None => {
+                let scheme = self.scheme_for(self.host_config(&self.admin_host));
+                format!("{}://{}:{}", scheme, self.admin_host, self.port)
+            }
+        }
+    }
+
+    /// Scheme clients reach `site` over when we serve it directly.
+    pub fn scheme_for<'a>(&self, site: Option<&'a HostConfig>) -> &'a str {
+        match site {
+            _ if self.tls.is_some() => "https",
+            Some(site) => &site.default_scheme,
+            None => "http",
+        }
+    }
+
+    /// Whether forwarded headers from `peer` are believed.
+    pub fn trusts_proxy(&self, peer: IpAddr) -> bool {
+        let peer = match peer {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(peer, IpAddr::V4),
+            v4 => v4,
+        };
+        self.trusted_proxies.iter().any(|net| net.contains(&peer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Resolve `file` as if only `vars` were set in the environment.
+    fn resolve(file: FileConfig, vars: &[(&str, &str)]) -> Result<Config> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        Config::resolve(file, |name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            database_url = "memory:"
+
+            [[hosts]]
+            name = "lynx"
+            admin = true
+            default_scheme = "https"
+
+            [[hosts]]
+            name = "go"
+            aliases = ["go.corp.example.com"]
+
+            [[hosts]]
+            name = "docs"
+            default = true
+            on_miss = { redirect = "https://docs.example.com/search?q={path}" }
+
+            [[hosts]]
+            name = "t"
+            on_miss = "not_found"
+            "#,
+        )
+        .unwrap();
+
+        let config = resolve(file, &[]).unwrap();
+        assert_eq!(config.hosts.len(), 4);
+
+        let go = config.host_config("go.corp.example.com").unwrap();
+        assert_eq!(go.name, "go");
+        assert!(!go.admin);
+        assert_eq!(go.on_miss, MissBehaviour::AddLink);
+        assert_eq!(go.default_scheme, "http");
+
+        assert_eq!(config.host_config("t").unwrap().on_miss, MissBehaviour::NotFound);
+        assert!(config.host_config("unknown").is_none());
+
+        assert_eq!(config.admin_host, "lynx");
+        assert_eq!(config.default_redirect_host, "docs");
+        assert!(config.admin_url().starts_with("https://lynx:"));
+    }
+
+    #[test]
+    fn test_environment_overrides_hosts() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            database_url = "memory:"
+
+            [[hosts]]
+            name = "lynx"
+            admin = true
+
+            [[hosts]]
+            name = "docs"
+            default = true
+            "#,
+        )
+        .unwrap();
+
+        let config = resolve(file, &[("ADMIN_HOST", "Admin:8080"), ("DEFAULT_REDIRECT_HOST", "go")]).unwrap();
+        assert_eq!(config.admin_host, "admin");
+        assert_eq!(config.default_redirect_host, "go");
+        // Hosts named only through the environment get the defaults
+        assert!(config.host_config("admin").unwrap().admin);
+        assert!(!config.host_config("go").unwrap().admin);
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("go"), "go");
+        assert_eq!(normalize_host("GO:3100"), "go");
+        assert_eq!(normalize_host("Go.Corp.Example.com."), "go.corp.example.com");
+        assert_eq!(normalize_host("bücher.example"), "xn--bcher-kva.example");
+        assert_eq!(normalize_host("BÜCHER.example:8080"), "xn--bcher-kva.example");
+        assert_eq!(normalize_host("[::1]:3000"), "[::1]");
+        assert_eq!(normalize_host("127.0.0.1:3000"), "127.0.0.1");
+    }
+
+    #[test]
+    fn test_canonical_host() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            [[hosts]]
+            name = "Go"
+            aliases = ["go.corp.example.com", "gö.example"]
+            "#,
+        )
+        .unwrap();
+        let file = FileConfig {
+            database_url: Some("memory:".to_string()),
+            ..file
+        };
+        let config = resolve(file, &[]).unwrap();
+
+        assert_eq!(config.canonical_host("go"), "go");
+        assert_eq!(config.canonical_host("GO.corp.example.com:3100"), "go");
+        assert_eq!(config.canonical_host("xn--g-1ga.example"), "go");
+        assert_eq!(config.canonical_host("GÖ.example"), "go");
+        assert_eq!(config.canonical_host("Other:80"), "other");
+    }
+
+    #[test]
+    fn test_admin_url_behind_proxy() {
+        let file = FileConfig {
+            database_url: Some("memory:".to_string()),
+            trusted_proxies: vec![parse_proxy("10.0.0.0/8").unwrap(), parse_proxy("::1").unwrap()],
+            ..Default::default()
+        };
+        let mut config = resolve(file, &[]).unwrap();
+
+        assert!(config.trusts_proxy("10.1.2.3".parse().unwrap()));
+        assert!(config.trusts_proxy("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(config.trusts_proxy("::1".parse().unwrap()));
+        assert!(!config.trusts_proxy("192.168.1.1".parse().unwrap()));
+
+        assert_eq!(config.admin_url_for(Some("https")), "https://lynx");
+        assert_eq!(config.admin_url_for(None), "http://lynx:3100");
+
+        config.public_url = Some("https://lynx.example.com".to_string());
+        assert_eq!(config.admin_url_for(Some("http")), "https://lynx.example.com");
+        assert_eq!(config.admin_url(), "https://lynx.example.com");
+    }
+
+    #[test]
+    fn test_tls() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            database_url = "memory:"
+
+            [tls]
+            cert = "/etc/lynx/cert.pem"
+            key = "/etc/lynx/key.pem"
+            http_redirect_port = 80
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.tls.as_ref().unwrap().http_redirect_port, Some(80));
+
+        let config = resolve(file, &[]).unwrap();
+        assert_eq!(config.tls.as_ref().unwrap().cert, PathBuf::from("/etc/lynx/cert.pem"));
+        assert!(config.admin_url().starts_with("https://"));
+
+        let file: FileConfig = toml::from_str("database_url = \"memory:\"\n").unwrap();
+        assert!(resolve(file, &[("TLS_CERT", "cert.pem")]).is_err());
+
+        assert!(toml::from_str::<FileConfig>("[tls]\ncert = \"cert.pem\"\n").is_err());
+    }
+
+    #[test]
+    fn test_log_format() {
+        let file: FileConfig = toml::from_str("database_url = \"memory:\"\nlog_format = \"json\"\n").unwrap();
+        assert_eq!(file.log_format, Some(LogFormat::Json));
+        assert!(toml::from_str::<FileConfig>("log_format = \"xml\"\n").is_err());
+
+        let file: FileConfig = toml::from_str("database_url = \"memory:\"\n").unwrap();
+        assert_eq!(resolve(file, &[]).unwrap().log_format, LogFormat::Text);
+        let file: FileConfig = toml::from_str("database_url = \"memory:\"\nlog_format = \"text\"\n").unwrap();
+        assert_eq!(resolve(file, &[("LOG_FORMAT", "json")]).unwrap().log_format, LogFormat::Json);
+    }
+
+    #[test]
+    fn test_pool_config() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            database_url = "memory:"
+            snapshot_path = "/var/lib/lynx/cache.json"
+
+            [pool]
+            max_connections = 4
+            statement_timeout = 5000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.pool.max_connections, 4);
+        assert_eq!(file.pool.acquire_timeout, PoolConfig::default().acquire_timeout);
+
+        let config = resolve(file, &[("DB_MAX_CONNECTIONS", "8")]).unwrap();
+        assert_eq!(config.pool.max_connections, 8);
+        assert_eq!(config.pool.statement_timeout, 5000);
+        assert_eq!(config.snapshot_path, Some(PathBuf::from("/var/lib/lynx/cache.json")));
+
+        assert!(toml::from_str::<FileConfig>("[pool]\nmax_conns = 4\n").is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        assert!(toml::from_str::<FileConfig>("[[hosts]]\nname = \"go\"\nadmn = true\n").is_err());
+    }
 }
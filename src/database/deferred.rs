@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+
+use super::{LinkStore, ListLinksParams, PoolStats, SchemaStatus};
+use crate::batch::{BatchOperation, BatchReport, LinkChange};
+use crate::retarget::{Retarget, Rewrite};
+use crate::error::{DbError, DbResult};
+use crate::sync::{SyncPlan, SyncScope};
+use crate::models::{
+    ConflictPolicy, CreateLinkRequest, ImportReport, Link, LinkPage, Revision, UpdateLinkRequest,
+};
+
+/// A backend that is still being connected to. Every call fails as unavailable until
+/// [`Deferred::attach`] hands over the connected store, then calls go straight to it.
+#[derive(Default)]
+pub(super) struct Deferred {
+    store: OnceLock<Arc<dyn LinkStore>>,
+}
+
+impl Deferred {
+    pub(super) fn attach(&self, store: Arc<dyn LinkStore>) {
+        let _ = self.store.set(store);
+    }
+
+    fn store(&self) -> DbResult<&dyn LinkStore> {
+        self.store.get().map(Arc::as_ref).ok_or_else(not_connected)
+    }
+}
+
+fn not_connected() -> DbError {
+    DbError::Unavailable(sqlx::Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotConnected,
+        "still connecting to the database",
+    )))
+}
+
+#[async_trait]
+impl LinkStore for Deferred {
+    async fn migrate(&self) -> DbResult<()> {
+        self.store()?.migrate().await
+    }
+
+    async fn schema_status(&self) -> DbResult<SchemaStatus> {
+        self.store()?.schema_status().await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.store.get()?.pool_stats()
+    }
+
+    async fn get_all_links(&self) -> DbResult<Vec<Link>> {
+        self.store()?.get_all_links().await
+    }
+
+    fn stream_links(&self) -> BoxStream<'_, DbResult<Link>> {
+        match self.store() {
+            Ok(store) => store.stream_links(),
+            Err(e) => stream::once(async { Err(e) }).boxed(),
+        }
+    }
+
+    async fn get_link_by_id(&self, id: i32) -> DbResult<Option<Link>> {
+        self.store()?.get_link_by_id(id).await
+    }
+
+    async fn create_link(&self, request: &CreateLinkRequest) -> DbResult<Link> {
+        self.store()?.create_link(request).await
+    }
+
+    async fn update_link(&self, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        self.store()?.update_link(id, request, expected_version).await
+    }
+
+    async fn delete_link(&self, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        self.store()?.delete_link(id, expected_version).await
+    }
+
+    async fn search_links(&self, query: &str, page: i32, per_page: i32) -> DbResult<Vec<Link>> {
+        self.store()?.search_links(query, page, per_page).await
+    }
+
+    async fn get_recent_links(&self, limit: i32) -> DbResult<Vec<Link>> {
+        self.store()?.get_recent_links(limit).await
+    }
+
+    async fn get_link_by_host_and_source(&self, host: &str, source: &str) -> DbResult<Option<Link>> {
+        self.store()?.get_link_by_host_and_source(host, source).await
+    }
+
+    async fn check_source_conflict(&self, host: &str, source: &str, exclude_id: Option<i32>) -> DbResult<bool> {
+        self.store()?.check_source_conflict(host, source, exclude_id).await
+    }
+
+    async fn existing_sources(&self, hosts: &[&str]) -> DbResult<HashSet<(String, String)>> {
+        self.store()?.existing_sources(hosts).await
+    }
+
+    async fn get_links_by_target(&self, target: &str) -> DbResult<Vec<Link>> {
+        self.store()?.get_links_by_target(target).await
+    }
+
+    async fn list_links(&self, params: &ListLinksParams) -> DbResult<LinkPage> {
+        self.store()?.list_links(params).await
+    }
+
+    async fn count_links(&self, host: Option<&str>) -> DbResult<i64> {
+        self.store()?.count_links(host).await
+    }
+
+    async fn record_hit(&self, id: i32) -> DbResult<()> {
+        self.store()?.record_hit(id).await
+    }
+
+    async fn preview_import(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<ImportReport> {
+        self.store()?.preview_import(links, policy).await
+    }
+
+    async fn import_links(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<(ImportReport, Vec<Link>)> {
+        self.store()?.import_links(links, policy).await
+    }
+
+    async fn sync_links(&self, scope: &SyncScope, desired: &[CreateLinkRequest], apply: bool) -> DbResult<(SyncPlan, Vec<Link>)> {
+        self.store()?.sync_links(scope, desired, apply).await
+    }
+
+    async fn apply_batch(&self, operations: &[BatchOperation]) -> DbResult<(BatchReport, Vec<LinkChange>)> {
+        self.store()?.apply_batch(operations).await
+    }
+
+    async fn retarget_links(&self, rewrite: &Rewrite, apply: bool) -> DbResult<(Vec<Retarget>, Vec<Link>)> {
+        self.store()?.retarget_links(rewrite, apply).await
+    }
+
+    async fn get_revisions(&self, link_id: i32) -> DbResult<Vec<Revision>> {
+        self.store()?.get_revisions(link_id).await
+    }
+}
@@ -0,0 +1,422 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::{stream::{self, BoxStream}, StreamExt};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::RwLock;
+
+use super::{check_version, import_conflict, LinkStore, ListLinksParams, SchemaStatus};
+use crate::batch::{BatchOperation, BatchReport, BatchResult, LinkChange};
+use crate::retarget::{Retarget, Rewrite};
+use crate::error::{DbError, DbResult};
+use crate::sync::{SyncPlan, SyncScope};
+use crate::models::{
+    ConflictPolicy, CreateLinkRequest, CursorKey, ImportReport, Link, LinkCursor, LinkPage, Revision,
+    SortOrder, UpdateLinkRequest,
+};
+
+/// Links held in process memory, for tests and throwaway instances. Nothing is persisted.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: RwLock<MemoryState>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MemoryState {
+    links: BTreeMap<i32, Link>,
+    last_id: i32,
+    revisions: Vec<Revision>,
+    last_revision_id: i32,
+}
+
+impl MemoryState {
+    fn find(&self, host: &str, source: &str) -> Option<&Link> {
+        self.links.values().find(|link| link.host == host && link.source == source)
+    }
+
+    fn insert(&mut self, host: &str, source: &str, target: &str) -> DbResult<Link> {
+        if self.find(host, source).is_some() {
+            return Err(DbError::Conflict {
+                host: host.to_string(),
+                source: source.to_string(),
+            });
+        }
+
+        self.last_id += 1;
+        let now = Utc::now();
+        let link = Link {
+            id: self.last_id,
+            host: host.to_string(),
+            source: source.to_string(),
+            target: target.to_string(),
+            created_at: now,
+            updated_at: now,
+            hit_count: 0,
+            version: 1,
+        };
+        self.links.insert(link.id, link.clone());
+        Ok(link)
+    }
+
+    fn update(&mut self, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        let existing = self.links.get(&id).ok_or(DbError::NotFound)?;
+        let before = existing.clone();
+        check_version(existing, expected_version)?;
+
+        let host = request.host.clone().unwrap_or_else(|| existing.host.clone());
+        let source = request.source.clone().unwrap_or_else(|| existing.source.clone());
+        if self.find(&host, &source).is_some_and(|other| other.id != id) {
+            return Err(DbError::Conflict { host, source });
+        }
+
+        let link = self.links.get_mut(&id).ok_or(DbError::NotFound)?;
+        link.host = host;
+        link.source = source;
+        if let Some(target) = &request.target {
+            link.target = target.clone();
+        }
+        link.updated_at = Utc::now();
+        link.version += 1;
+        Ok(LinkChange { before: Some(before), after: Some(link.clone()) })
+    }
+
+    fn remove(&mut self, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        check_version(self.links.get(&id).ok_or(DbError::NotFound)?, expected_version)?;
+        self.revisions.retain(|revision| revision.link_id != id);
+        let before = self.links.remove(&id).ok_or(DbError::NotFound)?;
+        Ok(LinkChange { before: Some(before), after: None })
+    }
+
+    fn apply(&mut self, operation: &BatchOperation) -> DbResult<LinkChange> {
+        Ok(match operation {
+            BatchOperation::Create(request) => LinkChange {
+                before: None,
+                after: Some(self.insert(&request.host, &request.source, &request.target)?),
+            },
+            BatchOperation::Update { id, version, changes } => self.update(*id, changes, *version)?,
+            BatchOperation::Delete { id, version } => self.remove(*id, *version)?,
+        })
+    }
+
+    fn set_target(&mut self, id: i32, target: &str) -> Option<Link> {
+        let link = self.links.get_mut(&id)?;
+        link.target = target.to_string();
+        link.updated_at = Utc::now();
+        link.version += 1;
+        Some(link.clone())
+    }
+}
+
+fn compare_keys(a: &CursorKey, b: &CursorKey) -> Ordering {
+    match (a, b) {
+        (CursorKey::Time(a), CursorKey::Time(b)) => a.cmp(b),
+        (CursorKey::Text(a), CursorKey::Text(b)) => a.cmp(b),
+        (CursorKey::Count(a), CursorKey::Count(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+fn newest_first(links: &mut [Link]) {
+    links.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+}
+
+impl MemoryStore {
+    /// A store holding copies of `links`, ids and all.
+    pub fn from_links(links: impl IntoIterator<Item = Link>) -> Self {
+        let links: BTreeMap<i32, Link> = links.into_iter().map(|link| (link.id, link)).collect();
+        let last_id = links.keys().next_back().copied().unwrap_or_default();
+        Self {
+            state: RwLock::new(MemoryState {
+                links,
+                last_id,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, MemoryState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, MemoryState> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl LinkStore for MemoryStore {
+    async fn migrate(&self) -> DbResult<()> {
+        Ok(())
+    }
+
+    async fn schema_status(&self) -> DbResult<SchemaStatus> {
+        Ok(SchemaStatus::default())
+    }
+
+    async fn get_all_links(&self) -> DbResult<Vec<Link>> {
+        let mut links: Vec<Link> = self.read().links.values().cloned().collect();
+        newest_first(&mut links);
+        Ok(links)
+    }
+
+    fn stream_links(&self) -> BoxStream<'_, DbResult<Link>> {
+        let mut links: Vec<Link> = self.read().links.values().cloned().collect();
+        links.sort_by(|a, b| (&a.host, &a.source, a.id).cmp(&(&b.host, &b.source, b.id)));
+        stream::iter(links.into_iter().map(Ok)).boxed()
+    }
+
+    async fn get_link_by_id(&self, id: i32) -> DbResult<Option<Link>> {
+        Ok(self.read().links.get(&id).cloned())
+    }
+
+    async fn create_link(&self, request: &CreateLinkRequest) -> DbResult<Link> {
+        self.write().insert(&request.host, &request.source, &request.target)
+    }
+
+    async fn update_link(&self, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        self.write().update(id, request, expected_version)
+    }
+
+    async fn delete_link(&self, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        self.write().remove(id, expected_version)
+    }
+
+    async fn search_links(&self, query: &str, page: i32, per_page: i32) -> DbResult<Vec<Link>> {
+        let query = query.to_lowercase();
+        let mut links: Vec<Link> = self
+            .read()
+            .links
+            .values()
+            .filter(|link| {
+                [&link.source, &link.target, &link.host]
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&query))
+            })
+            .cloned()
+            .collect();
+        newest_first(&mut links);
+
+        let offset = ((page - 1) * per_page).max(0) as usize;
+        Ok(links.into_iter().skip(offset).take(per_page.max(0) as usize).collect())
+    }
+
+    async fn get_recent_links(&self, limit: i32) -> DbResult<Vec<Link>> {
+        let mut links = self.get_all_links().await?;
+        links.truncate(limit.max(0) as usize);
+        Ok(links)
+    }
+
+    async fn get_link_by_host_and_source(&self, host: &str, source: &str) -> DbResult<Option<Link>> {
+        Ok(self.read().find(host, source).cloned())
+    }
+
+    async fn check_source_conflict(&self, host: &str, source: &str, exclude_id: Option<i32>) -> DbResult<bool> {
+        Ok(self.read().find(host, source).is_some_and(|link| Some(link.id) != exclude_id))
+    }
+
+    async fn existing_sources(&self, hosts: &[&str]) -> DbResult<HashSet<(String, String)>> {
+        Ok(self
+            .read()
+            .links
+            .values()
+            .filter(|link| hosts.contains(&link.host.as_str()))
+            .map(|link| (link.host.clone(), link.source.clone()))
+            .collect())
+    }
+
+    async fn get_links_by_target(&self, target: &str) -> DbResult<Vec<Link>> {
+        let mut links: Vec<Link> = self.read().links.values().filter(|link| link.target == target).cloned().collect();
+        newest_first(&mut links);
+        Ok(links)
+    }
+
+    async fn list_links(&self, params: &ListLinksParams) -> DbResult<LinkPage> {
+        let position = |link: &Link| (LinkCursor::for_link(link, params.sort).key, link.id);
+        let compare = |a: &(CursorKey, i32), b: &(CursorKey, i32)| {
+            let ordering = compare_keys(&a.0, &b.0).then(a.1.cmp(&b.1));
+            match params.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        };
+
+        let state = self.read();
+        let in_host = |link: &&Link| params.host.as_ref().is_none_or(|host| &link.host == host);
+        let total = state.links.values().filter(in_host).count() as i64;
+
+        let mut links: Vec<Link> = state
+            .links
+            .values()
+            .filter(in_host)
+            .filter(|link| match &params.cursor {
+                Some(cursor) => compare(&position(link), &(cursor.key.clone(), cursor.id)) == Ordering::Greater,
+                None => true,
+            })
+            .cloned()
+            .collect();
+        links.sort_by(|a, b| compare(&position(a), &position(b)));
+
+        let next_cursor = if links.len() as i64 > params.limit {
+            links.truncate(params.limit as usize);
+            links.last().map(|link| LinkCursor::for_link(link, params.sort))
+        } else {
+            None
+        };
+
+        Ok(LinkPage { links, next_cursor, total })
+    }
+
+    async fn count_links(&self, host: Option<&str>) -> DbResult<i64> {
+        let state = self.read();
+        Ok(state.links.values().filter(|link| host.is_none_or(|host| link.host == host)).count() as i64)
+    }
+
+    async fn record_hit(&self, id: i32) -> DbResult<()> {
+        if let Some(link) = self.write().links.get_mut(&id) {
+            link.hit_count += 1;
+        }
+        Ok(())
+    }
+
+    async fn import_links(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<(ImportReport, Vec<Link>)> {
+        let mut report = ImportReport {
+            total: links.len(),
+            ..Default::default()
+        };
+        let mut written = Vec::new();
+
+        // Work on a copy so a failed import leaves nothing behind
+        let mut guard = self.write();
+        let mut state = guard.clone();
+
+        for (index, link) in links.iter().enumerate() {
+            match state.find(&link.host, &link.source).map(|existing| existing.id) {
+                None => {
+                    written.push(state.insert(&link.host, &link.source, &link.target)?);
+                    report.created += 1;
+                }
+                Some(id) if policy == ConflictPolicy::Overwrite => {
+                    written.extend(state.set_target(id, &link.target));
+                    report.updated += 1;
+                    report.conflicts.push(import_conflict(index, link, "overwritten"));
+                }
+                Some(_) => {
+                    report.conflicts.push(import_conflict(index, link, "already exists"));
+                    if policy == ConflictPolicy::Fail {
+                        report.created = 0;
+                        return Ok((report, Vec::new()));
+                    }
+                    report.skipped += 1;
+                }
+            }
+        }
+
+        *guard = state;
+        report.applied = true;
+
+        Ok((report, written))
+    }
+
+    async fn sync_links(&self, scope: &SyncScope, desired: &[CreateLinkRequest], apply: bool) -> DbResult<(SyncPlan, Vec<Link>)> {
+        let mut state = self.write();
+
+        let mut existing: Vec<Link> = state
+            .links
+            .values()
+            .filter(|link| scope.contains(&link.host, &link.source))
+            .cloned()
+            .collect();
+        existing.sort_by(|a, b| a.source.cmp(&b.source));
+
+        let plan = SyncPlan::compute(&existing, desired);
+        if !apply || plan.is_empty() {
+            return Ok((plan, Vec::new()));
+        }
+
+        let mut next = state.clone();
+        for link in &plan.delete {
+            next.links.remove(&link.id);
+        }
+
+        let mut written = Vec::new();
+        for link in &plan.update {
+            written.extend(next.set_target(link.id, &link.new_target));
+        }
+        for link in &plan.create {
+            written.push(next.insert(&link.host, &link.source, &link.target)?);
+        }
+
+        *state = next;
+
+        Ok((plan, written))
+    }
+
+    async fn apply_batch(&self, operations: &[BatchOperation]) -> DbResult<(BatchReport, Vec<LinkChange>)> {
+        let mut state = self.write();
+
+        // Work on a copy, kept only if every operation succeeds
+        let mut next = state.clone();
+        let mut results = Vec::new();
+        let mut changes = Vec::new();
+        for (index, operation) in operations.iter().enumerate() {
+            match next.apply(operation) {
+                Ok(change) => {
+                    results.push(BatchResult::ok(index, &change));
+                    changes.push(change);
+                }
+                Err(e) => results.push(BatchResult::failed(index, &e)),
+            }
+        }
+
+        let report = BatchReport::new(results);
+        if !report.applied {
+            return Ok((report, Vec::new()));
+        }
+        *state = next;
+
+        Ok((report, changes))
+    }
+
+    async fn retarget_links(&self, rewrite: &Rewrite, apply: bool) -> DbResult<(Vec<Retarget>, Vec<Link>)> {
+        let mut state = self.write();
+
+        let mut links: Vec<Link> = state.links.values().cloned().collect();
+        links.sort_by(|a, b| (&a.host, &a.source).cmp(&(&b.host, &b.source)));
+        let changes = rewrite.plan(&links);
+        if !apply {
+            return Ok((changes, Vec::new()));
+        }
+        rewrite.check_preview(&changes)?;
+
+        let reason = rewrite.describe();
+        let mut written = Vec::new();
+        for change in &changes {
+            let link = state.set_target(change.id, &change.new_target).ok_or(DbError::NotFound)?;
+            state.last_revision_id += 1;
+            let revision = Revision {
+                id: state.last_revision_id,
+                link_id: link.id,
+                version: link.version,
+                old_target: change.old_target.clone(),
+                new_target: change.new_target.clone(),
+                reason: reason.clone(),
+                created_at: link.updated_at,
+            };
+            state.revisions.push(revision);
+            written.push(link);
+        }
+
+        Ok((changes, written))
+    }
+
+    async fn get_revisions(&self, link_id: i32) -> DbResult<Vec<Revision>> {
+        Ok(self
+            .read()
+            .revisions
+            .iter()
+            .rev()
+            .filter(|revision| revision.link_id == link_id)
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,577 @@
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use sqlx::migrate::Migrator;
+use std::collections::HashSet;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod deferred;
+mod memory;
+mod postgres;
+mod read_only;
+mod sqlite;
+mod timed;
+
+pub use memory::MemoryStore;
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+use crate::batch::{BatchOperation, BatchReport, LinkChange};
+use crate::config::PoolConfig;
+use crate::retarget::{Retarget, Rewrite};
+use crate::error::{DbError, DbResult};
+use crate::sync::{SyncPlan, SyncScope};
+use crate::models::{
+    ConflictPolicy, CreateLinkRequest, ImportConflict, ImportReport, Link, LinkCursor, LinkPage,
+    LinkSort, Revision, SortOrder, UpdateLinkRequest,
+};
+
+/// Parameters for a single page of [`LinkStore::list_links`].
+#[derive(Debug, Clone)]
+pub struct ListLinksParams {
+    pub host: Option<String>,
+    pub sort: LinkSort,
+    pub order: SortOrder,
+    pub cursor: Option<LinkCursor>,
+    pub limit: i64,
+}
+
+/// Applied migrations compared with the ones built into this binary.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaStatus {
+    /// This binary's migrations that haven't been applied
+    pub pending: usize,
+    /// Applied migrations this binary doesn't know, left by a newer version
+    pub unknown: Vec<i64>,
+}
+
+impl SchemaStatus {
+    /// Fail if a newer version of Lynx has migrated the database, as this one may
+    /// misread the schema or undo its changes.
+    pub fn ensure_compatible(&self) -> DbResult<()> {
+        if self.unknown.is_empty() {
+            return Ok(());
+        }
+        Err(DbError::Other(anyhow::anyhow!(
+            "The database schema is newer than this version of Lynx (unknown migrations {:?}); upgrade Lynx",
+            self.unknown
+        )))
+    }
+}
+
+/// Connection pool occupancy, for backends that keep one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Open connections, idle or in use
+    pub size: u32,
+    pub idle: usize,
+    pub max: u32,
+}
+
+/// Storage for links. Every backend enforces the `(host, source)` uniqueness itself and
+/// reports violations as [`DbError::Conflict`].
+#[async_trait]
+pub trait LinkStore: Send + Sync {
+    /// Apply pending migrations, refusing to touch a schema newer than this binary.
+    async fn migrate(&self) -> DbResult<()>;
+
+    /// How the applied migrations compare with this binary's. Also serves as a check that
+    /// the database is reachable.
+    async fn schema_status(&self) -> DbResult<SchemaStatus>;
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+
+    async fn get_all_links(&self) -> DbResult<Vec<Link>>;
+
+    /// Stream every link ordered by host and source, without buffering the whole table.
+    fn stream_links(&self) -> BoxStream<'_, DbResult<Link>>;
+
+    async fn get_link_by_id(&self, id: i32) -> DbResult<Option<Link>>;
+
+    async fn create_link(&self, request: &CreateLinkRequest) -> DbResult<Link>;
+
+    /// Change a link, failing with [`DbError::Stale`] unless it's still at `expected_version`
+    /// (`None` skips that check). Returns the link as it was and as it is now, both read in
+    /// the transaction that changed it.
+    async fn update_link(&self, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange>;
+
+    /// Remove a link, failing with [`DbError::Stale`] unless it's still at `expected_version`.
+    /// Returns the link as it was when removed.
+    async fn delete_link(&self, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange>;
+
+    async fn search_links(&self, query: &str, page: i32, per_page: i32) -> DbResult<Vec<Link>>;
+
+    async fn get_recent_links(&self, limit: i32) -> DbResult<Vec<Link>>;
+
+    async fn get_link_by_host_and_source(&self, host: &str, source: &str) -> DbResult<Option<Link>>;
+
+    async fn check_source_conflict(&self, host: &str, source: &str, exclude_id: Option<i32>) -> DbResult<bool>;
+
+    /// The `(host, source)` of every link on any of `hosts`.
+    async fn existing_sources(&self, hosts: &[&str]) -> DbResult<HashSet<(String, String)>>;
+
+    async fn get_links_by_target(&self, target: &str) -> DbResult<Vec<Link>>;
+
+    async fn list_links(&self, params: &ListLinksParams) -> DbResult<LinkPage>;
+
+    async fn count_links(&self, host: Option<&str>) -> DbResult<i64>;
+
+    async fn record_hit(&self, id: i32) -> DbResult<()>;
+
+    /// Report what [`LinkStore::import_links`] would do without writing anything.
+    async fn preview_import(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<ImportReport> {
+        let mut report = ImportReport {
+            dry_run: true,
+            total: links.len(),
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        let mut hosts: Vec<&str> = links.iter().map(|link| link.host.as_str()).collect();
+        hosts.sort_unstable();
+        hosts.dedup();
+        let existing = self.existing_sources(&hosts).await?;
+
+        for (index, link) in links.iter().enumerate() {
+            let reason = if !seen.insert((link.host.as_str(), link.source.as_str())) {
+                Some("duplicate in import")
+            } else if existing.contains(&(link.host.clone(), link.source.clone())) {
+                Some("already exists")
+            } else {
+                None
+            };
+
+            match reason {
+                Some(reason) => {
+                    report.conflicts.push(import_conflict(index, link, reason));
+                    match policy {
+                        ConflictPolicy::Skip => report.skipped += 1,
+                        ConflictPolicy::Overwrite => report.updated += 1,
+                        ConflictPolicy::Fail => {}
+                    }
+                }
+                None => report.created += 1,
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Import links in a single transaction. With [`ConflictPolicy::Fail`] the first conflict
+    /// rolls everything back and is returned in the report with `applied` unset.
+    async fn import_links(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<(ImportReport, Vec<Link>)>;
+
+    /// Diff the links in `scope` against `desired` and, when `apply` is set, make the store
+    /// match in one transaction. Returns the plan and the links that were created or updated.
+    async fn sync_links(&self, scope: &SyncScope, desired: &[CreateLinkRequest], apply: bool) -> DbResult<(SyncPlan, Vec<Link>)>;
+
+    /// Apply `operations` in order in one transaction, committed only if every one succeeds.
+    /// Each is tried even after another fails, so the report covers them all. Returns the
+    /// report and, when committed, the changes made.
+    async fn apply_batch(&self, operations: &[BatchOperation]) -> DbResult<(BatchReport, Vec<LinkChange>)>;
+
+    /// Find the targets `rewrite` changes and, when `apply` is set, rewrite them in one
+    /// transaction, recording a revision for each. Returns the changes and the updated links.
+    async fn retarget_links(&self, rewrite: &Rewrite, apply: bool) -> DbResult<(Vec<Retarget>, Vec<Link>)>;
+
+    /// A link's revisions, newest first.
+    async fn get_revisions(&self, link_id: i32) -> DbResult<Vec<Revision>>;
+}
+
+/// Handle to the configured [`LinkStore`], cheap to clone.
+#[derive(Clone)]
+pub struct Database {
+    store: Arc<dyn LinkStore>,
+}
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+impl Database {
+    /// Connect to the backend named by the URL scheme: `postgres://`, `sqlite:` or `memory:`.
+    pub async fn new(database_url: &str) -> DbResult<Self> {
+        Ok(Self::from_arc(open(database_url, &PoolConfig::default()).await?))
+    }
+
+    /// Connect with `settings`, retrying with exponential backoff while the database is
+    /// unreachable, up to `settings.connect_retries` times.
+    pub async fn connect(database_url: &str, settings: &PoolConfig) -> DbResult<Self> {
+        let store = open_with_retries(database_url, settings, Some(settings.connect_retries)).await?;
+        Ok(Self::from_arc(store))
+    }
+
+    /// Connect with `settings`, trying only once.
+    pub async fn connect_once(database_url: &str, settings: &PoolConfig) -> DbResult<Self> {
+        Ok(Self::from_arc(open(database_url, settings).await?))
+    }
+
+    /// A handle that reports the database as unavailable until the returned future, which
+    /// retries for as long as the database stays unreachable, has connected it.
+    pub fn connect_later(database_url: &str, settings: &PoolConfig) -> (Self, impl Future<Output = DbResult<()>>) {
+        let deferred = Arc::new(deferred::Deferred::default());
+        let (database_url, settings) = (database_url.to_string(), settings.clone());
+        let connect = {
+            let deferred = deferred.clone();
+            async move {
+                deferred.attach(open_with_retries(&database_url, &settings, None).await?);
+                Ok(())
+            }
+        };
+        (Self::from_arc(deferred), connect)
+    }
+
+    pub fn from_store(store: impl LinkStore + 'static) -> Self {
+        Self::from_arc(Arc::new(store))
+    }
+
+    /// A handle that reads from `store` but refuses every change as unavailable.
+    pub fn read_only(store: impl LinkStore + 'static) -> Self {
+        Self::from_store(read_only::ReadOnly::new(store))
+    }
+
+    /// Whether both handles lead to the same store.
+    pub fn same_store(&self, other: &Database) -> bool {
+        Arc::ptr_eq(&self.store, &other.store)
+    }
+
+    // Every call through the handle is timed
+    fn from_arc(store: Arc<dyn LinkStore>) -> Self {
+        Self { store: Arc::new(timed::Timed::new(store)) }
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn LinkStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.store.as_ref()
+    }
+}
+
+/// The URL scheme, which picks the backend.
+pub fn scheme(database_url: &str) -> &str {
+    database_url.split_once(':').map(|(scheme, _)| scheme).unwrap_or_default()
+}
+
+async fn open(database_url: &str, settings: &PoolConfig) -> DbResult<Arc<dyn LinkStore>> {
+    let scheme = scheme(database_url);
+    Ok(match scheme {
+        "postgres" | "postgresql" => Arc::new(PostgresStore::connect(database_url, settings).await?),
+        "sqlite" => Arc::new(SqliteStore::connect(database_url, settings).await?),
+        "memory" => Arc::new(MemoryStore::default()),
+        _ => {
+            return Err(DbError::Other(anyhow::anyhow!(
+                "Unsupported DATABASE_URL scheme '{}' (expected postgres, sqlite or memory)",
+                scheme
+            )))
+        }
+    })
+}
+
+/// Keep trying to connect while the database is unreachable, `retries` more times or forever.
+/// Other errors, like bad credentials, won't fix themselves and are returned at once.
+async fn open_with_retries(database_url: &str, settings: &PoolConfig, retries: Option<u32>) -> DbResult<Arc<dyn LinkStore>> {
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        match open(database_url, settings).await {
+            Err(DbError::Unavailable(e)) if retries.is_none_or(|retries| attempt < retries) => {
+                attempt += 1;
+                tracing::warn!("Database unavailable, retrying in {}ms: {}", delay.as_millis(), e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Pool options for either backend from the configured settings.
+pub(crate) fn pool_options<DB: sqlx::Database>(settings: &PoolConfig) -> sqlx::pool::PoolOptions<DB> {
+    sqlx::pool::PoolOptions::new()
+        .max_connections(settings.max_connections)
+        .acquire_timeout(Duration::from_secs(settings.acquire_timeout))
+        .idle_timeout((settings.idle_timeout > 0).then(|| Duration::from_secs(settings.idle_timeout)))
+}
+
+/// Compare the `applied` migration versions with those in `migrator`.
+pub(crate) fn schema_status(migrator: &Migrator, applied: &[i64]) -> SchemaStatus {
+    let known: Vec<i64> = migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .collect();
+    SchemaStatus {
+        pending: known.iter().filter(|version| !applied.contains(version)).count(),
+        unknown: applied.iter().copied().filter(|version| !known.contains(version)).collect(),
+    }
+}
+
+pub(crate) fn check_version(link: &Link, expected_version: Option<i32>) -> DbResult<()> {
+    match expected_version {
+        Some(version) if version != link.version => Err(DbError::Stale),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn import_conflict(index: usize, link: &CreateLinkRequest, reason: &str) -> ImportConflict {
+    ImportConflict {
+        index,
+        host: link.host.clone(),
+        source: link.source.clone(),
+        target: link.target.clone(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retarget::{PreviewedLink, RetargetRequest};
+
+    fn request(host: &str, source: &str, target: &str) -> CreateLinkRequest {
+        CreateLinkRequest {
+            host: host.to_string(),
+            source: source.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    /// Behaviour every backend must share.
+    async fn exercise_store(db: Database) {
+        db.migrate().await.unwrap();
+        assert_eq!(db.schema_status().await.unwrap(), SchemaStatus::default());
+
+        let docs = db.create_link(&request("go", "/docs", "https://docs.example.com")).await.unwrap();
+        let wiki = db.create_link(&request("go", "/wiki", "https://wiki.example.com")).await.unwrap();
+        db.create_link(&request("t", "/docs", "https://t.example.com")).await.unwrap();
+
+        match db.create_link(&request("go", "/docs", "https://other.example.com")).await {
+            Err(DbError::Conflict { host, source }) => assert_eq!((host.as_str(), source.as_str()), ("go", "/docs")),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+
+        let rename = UpdateLinkRequest {
+            host: None,
+            source: Some("/docs".to_string()),
+            target: None,
+        };
+        assert!(matches!(db.update_link(wiki.id, &rename, None).await, Err(DbError::Conflict { .. })));
+        assert!(matches!(db.update_link(-1, &rename, None).await, Err(DbError::NotFound)));
+
+        let retarget = UpdateLinkRequest {
+            host: None,
+            source: None,
+            target: Some("https://docs.example.com/v2".to_string()),
+        };
+        assert!(matches!(db.update_link(docs.id, &retarget, Some(docs.version + 1)).await, Err(DbError::Stale)));
+        let change = db.update_link(docs.id, &retarget, Some(docs.version)).await.unwrap();
+        assert_eq!(change.before.as_ref().map(|link| link.version), Some(docs.version));
+        let updated = change.after.unwrap();
+        assert_eq!(updated.target, "https://docs.example.com/v2");
+        assert_eq!(updated.created_at, docs.created_at);
+        assert_eq!(updated.version, docs.version + 1);
+
+        // Hits don't count as changes
+        db.record_hit(wiki.id).await.unwrap();
+        let hit = db.get_link_by_id(wiki.id).await.unwrap().unwrap();
+        assert_eq!((hit.hit_count, hit.version), (1, wiki.version));
+        assert!(db.check_source_conflict("go", "/wiki", None).await.unwrap());
+        assert!(!db.check_source_conflict("go", "/wiki", Some(wiki.id)).await.unwrap());
+        let existing = db.existing_sources(&["go", "nowhere"]).await.unwrap();
+        assert_eq!(existing, HashSet::from([("go".to_string(), "/docs".to_string()), ("go".to_string(), "/wiki".to_string())]));
+        assert!(db.existing_sources(&[]).await.unwrap().is_empty());
+        let preview = db
+            .preview_import(&[request("go", "/wiki", "https://a.example.com"), request("t", "/wiki", "https://b.example.com")], ConflictPolicy::Skip)
+            .await
+            .unwrap();
+        assert_eq!((preview.created, preview.skipped), (1, 1));
+        assert_eq!(db.search_links("WIKI", 1, 20).await.unwrap().len(), 1);
+
+        // Walk every sort order a page at a time
+        for sort in [LinkSort::Created, LinkSort::Updated, LinkSort::Source, LinkSort::Hits] {
+            let mut params = ListLinksParams {
+                host: Some("go".to_string()),
+                sort,
+                order: sort.default_order(),
+                cursor: None,
+                limit: 1,
+            };
+            let mut seen = Vec::new();
+            loop {
+                let page = db.list_links(&params).await.unwrap();
+                assert_eq!(page.total, 2);
+                seen.extend(page.links.iter().map(|link| link.id));
+                match page.next_cursor {
+                    Some(cursor) => params.cursor = Some(cursor),
+                    None => break,
+                }
+            }
+            seen.sort();
+            assert_eq!(seen, vec![docs.id, wiki.id], "sorting by {:?}", sort);
+        }
+
+        let import = vec![request("go", "/new", "https://new.example.com"), request("go", "/wiki", "https://wiki2.example.com")];
+        let (report, written) = db.import_links(&import, ConflictPolicy::Fail).await.unwrap();
+        assert!(!report.applied);
+        assert!(written.is_empty());
+        assert!(db.get_link_by_host_and_source("go", "/new").await.unwrap().is_none());
+
+        let (report, written) = db.import_links(&import, ConflictPolicy::Overwrite).await.unwrap();
+        assert!(report.applied);
+        assert_eq!((report.created, report.updated), (1, 1));
+        assert_eq!(written.len(), 2);
+        assert!(written.iter().any(|link| link.source == "/wiki" && link.version == wiki.version + 1));
+
+        // A scope covers whole path segments only: /newsletter is outside /new
+        let newsletter = db.create_link(&request("go", "/newsletter", "https://news.example.com")).await.unwrap();
+        let scope = SyncScope {
+            host: "go".to_string(),
+            prefix: "/new".to_string(),
+        };
+        let (plan, _) = db.sync_links(&scope, &[], true).await.unwrap();
+        assert_eq!(plan.delete.iter().map(|link| link.source.as_str()).collect::<Vec<_>>(), vec!["/new"]);
+        assert!(db.get_link_by_id(newsletter.id).await.unwrap().is_some());
+
+        let scope = SyncScope {
+            host: "go".to_string(),
+            prefix: "/".to_string(),
+        };
+        let desired = vec![request("go", "/docs", "https://docs.example.com/v3"), request("go", "/sync", "https://sync.example.com")];
+        let (plan, _) = db.sync_links(&scope, &desired, true).await.unwrap();
+        assert_eq!((plan.create.len(), plan.update.len(), plan.delete.len()), (1, 1, 2));
+        assert_eq!(db.count_links(Some("go")).await.unwrap(), 2);
+        assert_eq!(db.count_links(None).await.unwrap(), 3);
+
+        let exported: Vec<Link> = futures::TryStreamExt::try_collect(db.stream_links()).await.unwrap();
+        let keys: Vec<_> = exported.iter().map(|link| (link.host.as_str(), link.source.as_str())).collect();
+        assert_eq!(keys, vec![("go", "/docs"), ("go", "/sync"), ("t", "/docs")]);
+
+        // One bad operation keeps the whole batch out, but every operation is reported
+        let sync = db.get_link_by_host_and_source("go", "/sync").await.unwrap().unwrap();
+        let t_docs = db.get_link_by_host_and_source("t", "/docs").await.unwrap().unwrap();
+        let rename = |id, source: &str| BatchOperation::Update {
+            id,
+            version: None,
+            changes: UpdateLinkRequest {
+                host: None,
+                source: Some(source.to_string()),
+                target: None,
+            },
+        };
+        let operations = vec![
+            BatchOperation::Create(request("go", "/batch", "https://batch.example.com")),
+            rename(sync.id, "/docs"),
+            BatchOperation::Delete { id: -1, version: None },
+        ];
+        let (report, changes) = db.apply_batch(&operations).await.unwrap();
+        assert!(!report.applied);
+        assert!(changes.is_empty());
+        assert_eq!(report.results.iter().map(|result| result.ok).collect::<Vec<_>>(), vec![true, false, false]);
+        assert!(db.get_link_by_host_and_source("go", "/batch").await.unwrap().is_none());
+
+        let operations = vec![
+            BatchOperation::Create(request("go", "/batch", "https://batch.example.com")),
+            rename(sync.id, "/synced"),
+            BatchOperation::Delete { id: t_docs.id, version: Some(t_docs.version) },
+        ];
+        let (report, changes) = db.apply_batch(&operations).await.unwrap();
+        assert!(report.applied);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[1].before.as_ref().unwrap().source, "/sync");
+        assert_eq!(changes[1].after.as_ref().unwrap().source, "/synced");
+        assert!(db.get_link_by_id(t_docs.id).await.unwrap().is_none());
+        assert_eq!(db.count_links(None).await.unwrap(), 3);
+
+        let rewrite = |find: &str, replace: &str, regex| {
+            Rewrite::new(&RetargetRequest {
+                find: find.to_string(),
+                replace: replace.to_string(),
+                regex,
+                host: Some("go".to_string()),
+                previewed: None,
+            })
+            .unwrap()
+        };
+        let (changes, written) = db.retarget_links(&rewrite("example.com", "example.org", false), false).await.unwrap();
+        assert_eq!(changes.len(), 3);
+        assert!(written.is_empty());
+        assert!(db.get_revisions(docs.id).await.unwrap().is_empty());
+
+        // Applying a preview that no longer matches writes nothing
+        let mut request = RetargetRequest {
+            find: "example.com".to_string(),
+            replace: "example.org".to_string(),
+            regex: false,
+            host: Some("go".to_string()),
+            previewed: Some(changes.iter().skip(1).map(|change| PreviewedLink { id: change.id, version: change.version }).collect()),
+        };
+        assert!(matches!(db.retarget_links(&Rewrite::new(&request).unwrap(), true).await, Err(DbError::Stale)));
+        assert!(db.get_revisions(docs.id).await.unwrap().is_empty());
+        request.previewed = Some(changes.iter().map(|change| PreviewedLink { id: change.id, version: change.version }).collect());
+        let (changes, written) = db.retarget_links(&Rewrite::new(&request).unwrap(), true).await.unwrap();
+        assert_eq!((changes.len(), written.len()), (3, 3));
+        let (changes, written) = db.retarget_links(&rewrite(r"^https://docs\.(\w+)", "https://$1", true), true).await.unwrap();
+        assert_eq!((changes.len(), written.len()), (1, 1));
+        assert_eq!(written[0].target, "https://example.org/v3");
+
+        let revisions = db.get_revisions(docs.id).await.unwrap();
+        let targets: Vec<_> = revisions.iter().map(|revision| (revision.old_target.as_str(), revision.new_target.as_str())).collect();
+        assert_eq!(targets, vec![
+            ("https://docs.example.org/v3", "https://example.org/v3"),
+            ("https://docs.example.com/v3", "https://docs.example.org/v3"),
+        ]);
+        assert_eq!(revisions[0].version, written[0].version);
+        assert_eq!(revisions[1].reason, "Replaced \"example.com\" with \"example.org\"");
+
+        // The sync retargeted docs, so the version from before it is stale
+        assert!(matches!(db.delete_link(docs.id, Some(updated.version)).await, Err(DbError::Stale)));
+        let current = db.get_link_by_id(docs.id).await.unwrap().unwrap();
+        let change = db.delete_link(docs.id, Some(current.version)).await.unwrap();
+        assert_eq!(change.before.map(|link| link.target), Some(current.target));
+        assert!(change.after.is_none());
+        assert!(matches!(db.delete_link(docs.id, None).await, Err(DbError::NotFound)));
+        assert!(db.get_revisions(docs.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        exercise_store(Database::new("memory:").await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        exercise_store(Database::new("sqlite::memory:").await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_unknown_scheme_is_rejected() {
+        assert!(Database::new("mysql://localhost/lynx").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_retries_then_gives_up() {
+        let settings = PoolConfig {
+            acquire_timeout: 1,
+            connect_retries: 1,
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        let result = Database::connect("postgres://lynx@127.0.0.1:1/lynx", &settings).await;
+        assert!(matches!(result, Err(DbError::Unavailable(_))));
+        assert!(started.elapsed() >= FIRST_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_connect_later() {
+        let (db, connect) = Database::connect_later("memory:", &PoolConfig::default());
+        assert!(matches!(db.count_links(None).await, Err(DbError::Unavailable(_))));
+        assert!(futures::StreamExt::next(&mut db.stream_links()).await.unwrap().is_err());
+
+        connect.await.unwrap();
+        assert_eq!(db.count_links(None).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,587 @@
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use sqlx::{
+    migrate::Migrator,
+    postgres::{PgConnectOptions, PgConnection, PgListener, PgPoolOptions, PgRow},
+    Acquire, PgPool, Postgres, QueryBuilder, Row,
+};
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use super::{check_version, import_conflict, pool_options, schema_status, LinkStore, ListLinksParams, PoolStats, SchemaStatus};
+use crate::batch::{self, BatchOperation, BatchReport, BatchResult, LinkChange};
+use crate::retarget::{Retarget, Rewrite};
+use crate::config::PoolConfig;
+use crate::error::{DbError, DbResult};
+use crate::sync::{SyncPlan, SyncScope};
+use crate::models::{
+    ConflictPolicy, CreateLinkRequest, CursorKey, ImportReport, Link, LinkCursor, LinkPage, LinkSort,
+    Revision, SortOrder, UpdateLinkRequest,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Advisory lock held while migrating, so replicas starting together take turns.
+const MIGRATION_LOCK: i64 = 0x6c79_6e78_6d69_6772;
+
+const LINK_COLUMNS: &str = "id, host, source, target, created_at, updated_at, hit_count, version";
+
+fn link_from_row(row: &PgRow) -> Link {
+    Link {
+        id: row.get("id"),
+        host: row.get("host"),
+        source: row.get("source"),
+        target: row.get("target"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        hit_count: row.get("hit_count"),
+        version: row.get("version"),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    pub async fn connect(database_url: &str, settings: &PoolConfig) -> DbResult<Self> {
+        let mut options = PgConnectOptions::from_str(database_url)?;
+        if settings.statement_timeout > 0 {
+            options = options.options([("statement_timeout", format!("{}ms", settings.statement_timeout))]);
+        }
+        let pool: PgPoolOptions = pool_options(settings);
+        Ok(Self { pool: pool.connect_with(options).await? })
+    }
+
+    /// Whether `database_url` names a Postgres database, the only backend that can notify
+    /// other instances of changes.
+    pub fn handles(database_url: &str) -> bool {
+        matches!(super::scheme(database_url), "postgres" | "postgresql")
+    }
+
+    /// A dedicated connection for `LISTEN`, outside the pool.
+    pub async fn listener(database_url: &str) -> DbResult<PgListener> {
+        Ok(PgListener::connect(database_url).await?)
+    }
+}
+
+async fn read_schema_status(conn: &mut PgConnection) -> DbResult<SchemaStatus> {
+    let has_table: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(&mut *conn)
+        .await?;
+    let applied: Vec<i64> = if has_table {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(&mut *conn)
+            .await?
+    } else {
+        Vec::new()
+    };
+    Ok(schema_status(&MIGRATOR, &applied))
+}
+
+/// Check and migrate while holding [`MIGRATION_LOCK`]; whoever gets it second finds
+/// nothing left to do.
+async fn migrate_locked(conn: &mut PgConnection) -> DbResult<()> {
+    read_schema_status(conn).await?.ensure_compatible()?;
+    MIGRATOR.run_direct(conn).await?;
+    Ok(())
+}
+
+#[async_trait]
+impl LinkStore for PostgresStore {
+    async fn migrate(&self) -> DbResult<()> {
+        let mut conn = self.pool.acquire().await?;
+        // Waiting for the lock and running migrations may take longer than queries are allowed
+        sqlx::query("SET statement_timeout = 0").execute(&mut *conn).await?;
+        sqlx::query("SELECT pg_advisory_lock($1)").bind(MIGRATION_LOCK).execute(&mut *conn).await?;
+
+        let result = migrate_locked(&mut conn).await;
+
+        sqlx::query("SELECT pg_advisory_unlock($1)").bind(MIGRATION_LOCK).execute(&mut *conn).await?;
+        sqlx::query("RESET statement_timeout").execute(&mut *conn).await?;
+        result
+    }
+
+    async fn schema_status(&self) -> DbResult<SchemaStatus> {
+        read_schema_status(&mut *self.pool.acquire().await?).await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max: self.pool.options().get_max_connections(),
+        })
+    }
+
+    async fn get_all_links(&self) -> DbResult<Vec<Link>> {
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links ORDER BY created_at DESC"))
+            .fetch_all(&self.pool)
+            .await?;
+        
+        let mut links = Vec::new();
+        for row in rows {
+            links.push(link_from_row(&row));
+        }
+        
+        Ok(links)
+    }
+
+    fn stream_links(&self) -> BoxStream<'_, DbResult<Link>> {
+        // The stream borrows its SQL for as long as it runs, so build it once
+        static QUERY: OnceLock<String> = OnceLock::new();
+        let query = QUERY.get_or_init(|| format!("SELECT {LINK_COLUMNS} FROM links ORDER BY host, source, id"));
+        sqlx::query(query)
+            .fetch(&self.pool)
+            .map(|row| Ok(link_from_row(&row?)))
+            .boxed()
+    }
+
+    async fn get_link_by_id(&self, id: i32) -> DbResult<Option<Link>> {
+        let row = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        if let Some(row) = row {
+            Ok(Some(link_from_row(&row)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn create_link(&self, request: &CreateLinkRequest) -> DbResult<Link> {
+        let row = sqlx::query(&format!("INSERT INTO links (host, source, target) VALUES ($1, $2, $3) RETURNING {LINK_COLUMNS}"))
+            .bind(&request.host)
+            .bind(&request.source)
+            .bind(&request.target)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(DbError::conflict_on(&request.host, &request.source))?;
+        
+        Ok(link_from_row(&row))
+    }
+
+    async fn update_link(&self, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        let mut tx = self.pool.begin().await?;
+        let change = update_in(&mut tx, id, request, expected_version).await?;
+        tx.commit().await?;
+        Ok(change)
+    }
+
+    async fn delete_link(&self, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        let mut tx = self.pool.begin().await?;
+        let change = delete_in(&mut tx, id, expected_version).await?;
+        tx.commit().await?;
+        Ok(change)
+    }
+
+    async fn search_links(&self, query: &str, page: i32, per_page: i32) -> DbResult<Vec<Link>> {
+        let offset = (page - 1) * per_page;
+        let search_pattern = format!("%{}%", query);
+        
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE source ILIKE $1 OR target ILIKE $1 OR host ILIKE $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3"))
+            .bind(search_pattern)
+            .bind(per_page as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        
+        let mut links = Vec::new();
+        for row in rows {
+            links.push(link_from_row(&row));
+        }
+        
+        Ok(links)
+    }
+
+    async fn get_recent_links(&self, limit: i32) -> DbResult<Vec<Link>> {
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links ORDER BY created_at DESC LIMIT $1"))
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        
+        let mut links = Vec::new();
+        for row in rows {
+            links.push(link_from_row(&row));
+        }
+        
+        Ok(links)
+    }
+
+    async fn get_link_by_host_and_source(&self, host: &str, source: &str) -> DbResult<Option<Link>> {
+        let row = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE host = $1 AND source = $2"))
+            .bind(host)
+            .bind(source)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        if let Some(row) = row {
+            Ok(Some(link_from_row(&row)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn check_source_conflict(&self, host: &str, source: &str, exclude_id: Option<i32>) -> DbResult<bool> {
+        let count: i64 = if let Some(id) = exclude_id {
+            let row = sqlx::query("SELECT COUNT(*) as count FROM links WHERE host = $1 AND source = $2 AND id != $3")
+                .bind(host)
+                .bind(source)
+                .bind(id)
+                .fetch_one(&self.pool)
+                .await?;
+            row.get("count")
+        } else {
+            let row = sqlx::query("SELECT COUNT(*) as count FROM links WHERE host = $1 AND source = $2")
+                .bind(host)
+                .bind(source)
+                .fetch_one(&self.pool)
+                .await?;
+            row.get("count")
+        };
+        
+        Ok(count > 0)
+    }
+
+    async fn existing_sources(&self, hosts: &[&str]) -> DbResult<HashSet<(String, String)>> {
+        let rows = sqlx::query("SELECT host, source FROM links WHERE host = ANY($1)")
+            .bind(hosts)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(|row| (row.get("host"), row.get("source"))).collect())
+    }
+
+    async fn get_links_by_target(&self, target: &str) -> DbResult<Vec<Link>> {
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE target = $1 ORDER BY created_at DESC"))
+            .bind(target)
+            .fetch_all(&self.pool)
+            .await?;
+        
+        let mut links = Vec::new();
+        for row in rows {
+            links.push(link_from_row(&row));
+        }
+        
+        Ok(links)
+    }
+
+    async fn list_links(&self, params: &ListLinksParams) -> DbResult<LinkPage> {
+        let column = match params.sort {
+            LinkSort::Created => "created_at",
+            LinkSort::Updated => "updated_at",
+            LinkSort::Source => "source",
+            LinkSort::Hits => "hit_count",
+        };
+        let (direction, comparison) = match params.order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new(format!("SELECT {LINK_COLUMNS} FROM links WHERE TRUE"));
+        if let Some(host) = &params.host {
+            query.push(" AND host = ").push_bind(host.clone());
+        }
+        if let Some(cursor) = &params.cursor {
+            query.push(format!(" AND ({column}, id) {comparison} ("));
+            match &cursor.key {
+                CursorKey::Time(t) => query.push_bind(*t),
+                CursorKey::Text(s) => query.push_bind(s.clone()),
+                CursorKey::Count(n) => query.push_bind(*n),
+            };
+            query.push(", ").push_bind(cursor.id).push(")");
+        }
+        // Fetch one extra row to learn whether another page follows
+        query
+            .push(format!(" ORDER BY {column} {direction}, id {direction} LIMIT "))
+            .push_bind(params.limit + 1);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let mut links: Vec<Link> = rows.iter().map(link_from_row).collect();
+
+        let next_cursor = if links.len() as i64 > params.limit {
+            links.truncate(params.limit as usize);
+            links.last().map(|link| LinkCursor::for_link(link, params.sort))
+        } else {
+            None
+        };
+
+        let total = self.count_links(params.host.as_deref()).await?;
+
+        Ok(LinkPage { links, next_cursor, total })
+    }
+
+    async fn count_links(&self, host: Option<&str>) -> DbResult<i64> {
+        let row = match host {
+            Some(host) => sqlx::query("SELECT COUNT(*) as count FROM links WHERE host = $1")
+                .bind(host)
+                .fetch_one(&self.pool)
+                .await?,
+            None => sqlx::query("SELECT COUNT(*) as count FROM links")
+                .fetch_one(&self.pool)
+                .await?,
+        };
+
+        Ok(row.get("count"))
+    }
+
+    async fn record_hit(&self, id: i32) -> DbResult<()> {
+        sqlx::query("UPDATE links SET hit_count = hit_count + 1 WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn import_links(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<(ImportReport, Vec<Link>)> {
+        let on_conflict = match policy {
+            ConflictPolicy::Overwrite => "DO UPDATE SET target = EXCLUDED.target, updated_at = now(), version = links.version + 1",
+            ConflictPolicy::Skip | ConflictPolicy::Fail => "DO NOTHING",
+        };
+        let sql = format!(
+            "INSERT INTO links (host, source, target) VALUES ($1, $2, $3) ON CONFLICT (host, source) {on_conflict} RETURNING {LINK_COLUMNS}, (xmax = 0) AS inserted"
+        );
+
+        let mut report = ImportReport {
+            total: links.len(),
+            ..Default::default()
+        };
+        let mut written = Vec::new();
+        let mut tx = self.pool.begin().await?;
+
+        for (index, link) in links.iter().enumerate() {
+            let row = sqlx::query(&sql)
+                .bind(&link.host)
+                .bind(&link.source)
+                .bind(&link.target)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+            match row {
+                Some(row) => {
+                    if row.get::<bool, _>("inserted") {
+                        report.created += 1;
+                    } else {
+                        report.updated += 1;
+                        report.conflicts.push(import_conflict(index, link, "overwritten"));
+                    }
+                    written.push(link_from_row(&row));
+                }
+                None => {
+                    report.conflicts.push(import_conflict(index, link, "already exists"));
+                    if policy == ConflictPolicy::Fail {
+                        tx.rollback().await?;
+                        report.created = 0;
+                        return Ok((report, Vec::new()));
+                    }
+                    report.skipped += 1;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        report.applied = true;
+
+        Ok((report, written))
+    }
+
+    async fn sync_links(&self, scope: &SyncScope, desired: &[CreateLinkRequest], apply: bool) -> DbResult<(SyncPlan, Vec<Link>)> {
+        let mut tx = self.pool.begin().await?;
+
+        // When applying, lock the scope so the plan can't go stale before it is applied
+        let lock = if apply { " FOR UPDATE" } else { "" };
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE host = $1 AND (source = $2 OR starts_with(source, $2 || '/')) ORDER BY source{lock}"))
+            .bind(&scope.host)
+            .bind(scope.path_prefix())
+            .fetch_all(&mut *tx)
+            .await?;
+        let existing: Vec<Link> = rows.iter().map(link_from_row).collect();
+
+        let plan = SyncPlan::compute(&existing, desired);
+        if !apply || plan.is_empty() {
+            tx.rollback().await?;
+            return Ok((plan, Vec::new()));
+        }
+
+        let deleted: Vec<i32> = plan.delete.iter().map(|link| link.id).collect();
+        sqlx::query("DELETE FROM links WHERE id = ANY($1)")
+            .bind(&deleted)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut written = Vec::new();
+        for link in &plan.update {
+            let row = sqlx::query(&format!("UPDATE links SET target = $1, updated_at = now(), version = version + 1 WHERE id = $2 RETURNING {LINK_COLUMNS}"))
+                .bind(&link.new_target)
+                .bind(link.id)
+                .fetch_one(&mut *tx)
+                .await?;
+            written.push(link_from_row(&row));
+        }
+        for link in &plan.create {
+            let row = sqlx::query(&format!("INSERT INTO links (host, source, target) VALUES ($1, $2, $3) RETURNING {LINK_COLUMNS}"))
+                .bind(&link.host)
+                .bind(&link.source)
+                .bind(&link.target)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(DbError::conflict_on(&link.host, &link.source))?;
+            written.push(link_from_row(&row));
+        }
+
+        tx.commit().await?;
+
+        Ok((plan, written))
+    }
+
+    async fn apply_batch(&self, operations: &[BatchOperation]) -> DbResult<(BatchReport, Vec<LinkChange>)> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::new();
+        let mut changes = Vec::new();
+
+        for (index, operation) in operations.iter().enumerate() {
+            // A savepoint per operation, so one failing doesn't stop the rest being tried
+            let mut savepoint = tx.begin().await?;
+            match apply_operation(&mut savepoint, operation).await {
+                Ok(change) => {
+                    savepoint.commit().await?;
+                    results.push(BatchResult::ok(index, &change));
+                    changes.push(change);
+                }
+                Err(e) if batch::is_operation_error(&e) => {
+                    savepoint.rollback().await?;
+                    results.push(BatchResult::failed(index, &e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let report = BatchReport::new(results);
+        if !report.applied {
+            tx.rollback().await?;
+            return Ok((report, Vec::new()));
+        }
+        tx.commit().await?;
+
+        Ok((report, changes))
+    }
+
+    async fn retarget_links(&self, rewrite: &Rewrite, apply: bool) -> DbResult<(Vec<Retarget>, Vec<Link>)> {
+        let mut tx = self.pool.begin().await?;
+
+        // When applying, lock the matches so none change between being read and rewritten
+        let lock = if apply { " FOR UPDATE" } else { "" };
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE ($1::TEXT IS NULL OR host = $1) AND strpos(target, $2) > 0 ORDER BY host, source{lock}"))
+            .bind(rewrite.host.as_deref())
+            .bind(rewrite.required_text())
+            .fetch_all(&mut *tx)
+            .await?;
+        let candidates: Vec<Link> = rows.iter().map(link_from_row).collect();
+
+        let changes = rewrite.plan(&candidates);
+        if !apply {
+            tx.rollback().await?;
+            return Ok((changes, Vec::new()));
+        }
+        rewrite.check_preview(&changes)?;
+        if changes.is_empty() {
+            tx.rollback().await?;
+            return Ok((changes, Vec::new()));
+        }
+
+        let reason = rewrite.describe();
+        let mut written = Vec::new();
+        for change in &changes {
+            let row = sqlx::query(&format!("UPDATE links SET target = $1, updated_at = now(), version = version + 1 WHERE id = $2 RETURNING {LINK_COLUMNS}"))
+                .bind(&change.new_target)
+                .bind(change.id)
+                .fetch_one(&mut *tx)
+                .await?;
+            let link = link_from_row(&row);
+
+            sqlx::query("INSERT INTO link_revisions (link_id, version, old_target, new_target, reason) VALUES ($1, $2, $3, $4, $5)")
+                .bind(link.id)
+                .bind(link.version)
+                .bind(&change.old_target)
+                .bind(&change.new_target)
+                .bind(&reason)
+                .execute(&mut *tx)
+                .await?;
+            written.push(link);
+        }
+
+        tx.commit().await?;
+
+        Ok((changes, written))
+    }
+
+    async fn get_revisions(&self, link_id: i32) -> DbResult<Vec<Revision>> {
+        let revisions = sqlx::query_as("SELECT id, link_id, version, old_target, new_target, reason, created_at FROM link_revisions WHERE link_id = $1 ORDER BY id DESC")
+            .bind(link_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(revisions)
+    }
+}
+
+/// Apply one batch operation on the batch's transaction.
+async fn apply_operation(conn: &mut PgConnection, operation: &BatchOperation) -> DbResult<LinkChange> {
+    match operation {
+        BatchOperation::Create(request) => {
+            let row = sqlx::query(&format!("INSERT INTO links (host, source, target) VALUES ($1, $2, $3) RETURNING {LINK_COLUMNS}"))
+                .bind(&request.host)
+                .bind(&request.source)
+                .bind(&request.target)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(DbError::conflict_on(&request.host, &request.source))?;
+            Ok(LinkChange { before: None, after: Some(link_from_row(&row)) })
+        }
+        BatchOperation::Update { id, version, changes } => update_in(conn, *id, changes, *version).await,
+        BatchOperation::Delete { id, version } => delete_in(conn, *id, *version).await,
+    }
+}
+
+async fn update_in(conn: &mut PgConnection, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+    let existing = lock_link(conn, id).await?;
+    check_version(&existing, expected_version)?;
+
+    // Use existing values if not provided in update
+    let host = request.host.as_ref().unwrap_or(&existing.host);
+    let source = request.source.as_ref().unwrap_or(&existing.source);
+    let target = request.target.as_ref().unwrap_or(&existing.target);
+    let row = sqlx::query(&format!("UPDATE links SET host = $1, source = $2, target = $3, updated_at = now(), version = version + 1 WHERE id = $4 RETURNING {LINK_COLUMNS}"))
+        .bind(host)
+        .bind(source)
+        .bind(target)
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(DbError::conflict_on(host, source))?;
+    Ok(LinkChange { before: Some(existing), after: Some(link_from_row(&row)) })
+}
+
+async fn delete_in(conn: &mut PgConnection, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+    let existing = lock_link(conn, id).await?;
+    check_version(&existing, expected_version)?;
+
+    sqlx::query("DELETE FROM links WHERE id = $1")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(LinkChange { before: Some(existing), after: None })
+}
+
+/// The link with `id`, locked until the transaction ends.
+async fn lock_link(conn: &mut PgConnection, id: i32) -> DbResult<Link> {
+    let row = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE id = $1 FOR UPDATE"))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    row.as_ref().map(link_from_row).ok_or(DbError::NotFound)
+}
@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use std::collections::HashSet;
+
+use super::{LinkStore, ListLinksParams, PoolStats, SchemaStatus};
+use crate::batch::{BatchOperation, BatchReport, LinkChange};
+use crate::retarget::{Retarget, Rewrite};
+use crate::error::{DbError, DbResult};
+use crate::sync::{SyncPlan, SyncScope};
+use crate::models::{
+    ConflictPolicy, CreateLinkRequest, ImportReport, Link, LinkPage, Revision, UpdateLinkRequest,
+};
+
+/// A view of another store that serves reads and dry runs but refuses every change as
+/// unavailable, for serving from the link cache while the database is down.
+pub(super) struct ReadOnly<S> {
+    inner: S,
+}
+
+impl<S> ReadOnly<S> {
+    pub(super) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+fn read_only() -> DbError {
+    DbError::Unavailable(sqlx::Error::Io(std::io::Error::new(
+        std::io::ErrorKind::PermissionDenied,
+        "serving read-only from the link cache",
+    )))
+}
+
+#[async_trait]
+impl<S: LinkStore> LinkStore for ReadOnly<S> {
+    async fn migrate(&self) -> DbResult<()> {
+        Err(read_only())
+    }
+
+    async fn schema_status(&self) -> DbResult<SchemaStatus> {
+        self.inner.schema_status().await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.inner.pool_stats()
+    }
+
+    async fn get_all_links(&self) -> DbResult<Vec<Link>> {
+        self.inner.get_all_links().await
+    }
+
+    fn stream_links(&self) -> BoxStream<'_, DbResult<Link>> {
+        self.inner.stream_links()
+    }
+
+    async fn get_link_by_id(&self, id: i32) -> DbResult<Option<Link>> {
+        self.inner.get_link_by_id(id).await
+    }
+
+    async fn create_link(&self, _request: &CreateLinkRequest) -> DbResult<Link> {
+        Err(read_only())
+    }
+
+    async fn update_link(&self, _id: i32, _request: &UpdateLinkRequest, _expected_version: Option<i32>) -> DbResult<LinkChange> {
+        Err(read_only())
+    }
+
+    async fn delete_link(&self, _id: i32, _expected_version: Option<i32>) -> DbResult<LinkChange> {
+        Err(read_only())
+    }
+
+    async fn search_links(&self, query: &str, page: i32, per_page: i32) -> DbResult<Vec<Link>> {
+        self.inner.search_links(query, page, per_page).await
+    }
+
+    async fn get_recent_links(&self, limit: i32) -> DbResult<Vec<Link>> {
+        self.inner.get_recent_links(limit).await
+    }
+
+    async fn get_link_by_host_and_source(&self, host: &str, source: &str) -> DbResult<Option<Link>> {
+        self.inner.get_link_by_host_and_source(host, source).await
+    }
+
+    async fn check_source_conflict(&self, host: &str, source: &str, exclude_id: Option<i32>) -> DbResult<bool> {
+        self.inner.check_source_conflict(host, source, exclude_id).await
+    }
+
+    async fn existing_sources(&self, hosts: &[&str]) -> DbResult<HashSet<(String, String)>> {
+        self.inner.existing_sources(hosts).await
+    }
+
+    async fn get_links_by_target(&self, target: &str) -> DbResult<Vec<Link>> {
+        self.inner.get_links_by_target(target).await
+    }
+
+    async fn list_links(&self, params: &ListLinksParams) -> DbResult<LinkPage> {
+        self.inner.list_links(params).await
+    }
+
+    async fn count_links(&self, host: Option<&str>) -> DbResult<i64> {
+        self.inner.count_links(host).await
+    }
+
+    async fn record_hit(&self, _id: i32) -> DbResult<()> {
+        Err(read_only())
+    }
+
+    async fn preview_import(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<ImportReport> {
+        self.inner.preview_import(links, policy).await
+    }
+
+    async fn import_links(&self, _links: &[CreateLinkRequest], _policy: ConflictPolicy) -> DbResult<(ImportReport, Vec<Link>)> {
+        Err(read_only())
+    }
+
+    async fn sync_links(&self, scope: &SyncScope, desired: &[CreateLinkRequest], apply: bool) -> DbResult<(SyncPlan, Vec<Link>)> {
+        if apply {
+            return Err(read_only());
+        }
+        self.inner.sync_links(scope, desired, false).await
+    }
+
+    async fn apply_batch(&self, _operations: &[BatchOperation]) -> DbResult<(BatchReport, Vec<LinkChange>)> {
+        Err(read_only())
+    }
+
+    async fn retarget_links(&self, rewrite: &Rewrite, apply: bool) -> DbResult<(Vec<Retarget>, Vec<Link>)> {
+        if apply {
+            return Err(read_only());
+        }
+        self.inner.retarget_links(rewrite, false).await
+    }
+
+    async fn get_revisions(&self, link_id: i32) -> DbResult<Vec<Revision>> {
+        self.inner.get_revisions(link_id).await
+    }
+}
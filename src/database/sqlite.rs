@@ -0,0 +1,548 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{stream::BoxStream, StreamExt};
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePoolOptions, SqliteRow},
+    Acquire, QueryBuilder, Row, Sqlite, SqlitePool,
+};
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use super::{check_version, import_conflict, pool_options, schema_status, LinkStore, ListLinksParams, PoolStats, SchemaStatus};
+use crate::batch::{self, BatchOperation, BatchReport, BatchResult, LinkChange};
+use crate::retarget::{Retarget, Rewrite};
+use crate::config::PoolConfig;
+use crate::error::{DbError, DbResult};
+use crate::sync::{SyncPlan, SyncScope};
+use crate::models::{
+    ConflictPolicy, CreateLinkRequest, CursorKey, ImportReport, Link, LinkCursor, LinkPage, LinkSort,
+    Revision, SortOrder, UpdateLinkRequest,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+const LINK_COLUMNS: &str = "id, host, source, target, created_at, updated_at, hit_count, version";
+
+/// The current time in the fixed-width format the schema stores, so timestamps compare as text.
+const NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+fn link_from_row(row: &SqliteRow) -> Link {
+    Link {
+        id: row.get("id"),
+        host: row.get("host"),
+        source: row.get("source"),
+        target: row.get("target"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        hit_count: row.get("hit_count"),
+        version: row.get("version"),
+    }
+}
+
+/// Links in a SQLite file, for small single-node deployments. Other instances can't be
+/// notified of changes, so run only one instance per file.
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// `settings.statement_timeout` applies to Postgres only.
+    pub async fn connect(database_url: &str, settings: &PoolConfig) -> DbResult<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+
+        if !(database_url.contains(":memory:") || database_url.contains("mode=memory")) {
+            let pool: SqlitePoolOptions = pool_options(settings);
+            return Ok(Self { pool: pool.connect_with(options).await? });
+        }
+
+        // Every connection to an in-memory database gets its own empty database, so keep
+        // exactly one open for the life of the pool and create the schema up front
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+        let store = Self { pool };
+        store.migrate().await?;
+
+        Ok(store)
+    }
+}
+
+#[async_trait]
+impl LinkStore for SqliteStore {
+    // SQLite lets one writer in at a time, so concurrent runs can't interleave
+    async fn migrate(&self) -> DbResult<()> {
+        self.schema_status().await?.ensure_compatible()?;
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn schema_status(&self) -> DbResult<SchemaStatus> {
+        let has_table: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')")
+                .fetch_one(&self.pool)
+                .await?;
+        let applied: Vec<i64> = if has_table {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            Vec::new()
+        };
+        Ok(schema_status(&MIGRATOR, &applied))
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max: self.pool.options().get_max_connections(),
+        })
+    }
+
+    async fn get_all_links(&self) -> DbResult<Vec<Link>> {
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links ORDER BY created_at DESC, id DESC"))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(link_from_row).collect())
+    }
+
+    fn stream_links(&self) -> BoxStream<'_, DbResult<Link>> {
+        // The stream borrows its SQL for as long as it runs, so build it once
+        static QUERY: OnceLock<String> = OnceLock::new();
+        let query = QUERY.get_or_init(|| format!("SELECT {LINK_COLUMNS} FROM links ORDER BY host, source, id"));
+        sqlx::query(query)
+            .fetch(&self.pool)
+            .map(|row| Ok(link_from_row(&row?)))
+            .boxed()
+    }
+
+    async fn get_link_by_id(&self, id: i32) -> DbResult<Option<Link>> {
+        let row = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(link_from_row))
+    }
+
+    async fn create_link(&self, request: &CreateLinkRequest) -> DbResult<Link> {
+        let row = sqlx::query(&format!("INSERT INTO links (host, source, target) VALUES ($1, $2, $3) RETURNING {LINK_COLUMNS}"))
+            .bind(&request.host)
+            .bind(&request.source)
+            .bind(&request.target)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(DbError::conflict_on(&request.host, &request.source))?;
+
+        Ok(link_from_row(&row))
+    }
+
+    async fn update_link(&self, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        let mut tx = self.pool.begin().await?;
+        let change = update_in(&mut tx, id, request, expected_version).await?;
+        tx.commit().await?;
+        Ok(change)
+    }
+
+    async fn delete_link(&self, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        let mut tx = self.pool.begin().await?;
+        let change = delete_in(&mut tx, id, expected_version).await?;
+        tx.commit().await?;
+        Ok(change)
+    }
+
+    async fn search_links(&self, query: &str, page: i32, per_page: i32) -> DbResult<Vec<Link>> {
+        let offset = (page - 1) * per_page;
+        let search_pattern = format!("%{}%", query);
+
+        // LIKE is already case-insensitive for ASCII in SQLite
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE source LIKE $1 OR target LIKE $1 OR host LIKE $1 ORDER BY created_at DESC, id DESC LIMIT $2 OFFSET $3"))
+            .bind(search_pattern)
+            .bind(per_page as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(link_from_row).collect())
+    }
+
+    async fn get_recent_links(&self, limit: i32) -> DbResult<Vec<Link>> {
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links ORDER BY created_at DESC, id DESC LIMIT $1"))
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(link_from_row).collect())
+    }
+
+    async fn get_link_by_host_and_source(&self, host: &str, source: &str) -> DbResult<Option<Link>> {
+        let row = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE host = $1 AND source = $2"))
+            .bind(host)
+            .bind(source)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(link_from_row))
+    }
+
+    async fn check_source_conflict(&self, host: &str, source: &str, exclude_id: Option<i32>) -> DbResult<bool> {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM links WHERE host = $1 AND source = $2 AND id != $3")
+            .bind(host)
+            .bind(source)
+            .bind(exclude_id.unwrap_or(0))
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.get::<i64, _>("count") > 0)
+    }
+
+    async fn existing_sources(&self, hosts: &[&str]) -> DbResult<HashSet<(String, String)>> {
+        if hosts.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("SELECT host, source FROM links WHERE host IN (");
+        let mut separated = query.separated(", ");
+        for host in hosts {
+            separated.push_bind(*host);
+        }
+        separated.push_unseparated(")");
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        Ok(rows.iter().map(|row| (row.get("host"), row.get("source"))).collect())
+    }
+
+    async fn get_links_by_target(&self, target: &str) -> DbResult<Vec<Link>> {
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE target = $1 ORDER BY created_at DESC, id DESC"))
+            .bind(target)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(link_from_row).collect())
+    }
+
+    async fn list_links(&self, params: &ListLinksParams) -> DbResult<LinkPage> {
+        let column = match params.sort {
+            LinkSort::Created => "created_at",
+            LinkSort::Updated => "updated_at",
+            LinkSort::Source => "source",
+            LinkSort::Hits => "hit_count",
+        };
+        let (direction, comparison) = match params.order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(format!("SELECT {LINK_COLUMNS} FROM links WHERE 1"));
+        if let Some(host) = &params.host {
+            query.push(" AND host = ").push_bind(host.clone());
+        }
+        if let Some(cursor) = &params.cursor {
+            query.push(format!(" AND ({column}, id) {comparison} ("));
+            match &cursor.key {
+                CursorKey::Time(t) => query.push_bind(timestamp(*t)),
+                CursorKey::Text(s) => query.push_bind(s.clone()),
+                CursorKey::Count(n) => query.push_bind(*n),
+            };
+            query.push(", ").push_bind(cursor.id).push(")");
+        }
+        // Fetch one extra row to learn whether another page follows
+        query
+            .push(format!(" ORDER BY {column} {direction}, id {direction} LIMIT "))
+            .push_bind(params.limit + 1);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let mut links: Vec<Link> = rows.iter().map(link_from_row).collect();
+
+        let next_cursor = if links.len() as i64 > params.limit {
+            links.truncate(params.limit as usize);
+            links.last().map(|link| LinkCursor::for_link(link, params.sort))
+        } else {
+            None
+        };
+
+        let total = self.count_links(params.host.as_deref()).await?;
+
+        Ok(LinkPage { links, next_cursor, total })
+    }
+
+    async fn count_links(&self, host: Option<&str>) -> DbResult<i64> {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM links WHERE $1 IS NULL OR host = $1")
+            .bind(host)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    async fn record_hit(&self, id: i32) -> DbResult<()> {
+        sqlx::query("UPDATE links SET hit_count = hit_count + 1 WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn import_links(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<(ImportReport, Vec<Link>)> {
+        let mut report = ImportReport {
+            total: links.len(),
+            ..Default::default()
+        };
+        let mut written = Vec::new();
+        let mut tx = self.pool.begin().await?;
+
+        for (index, link) in links.iter().enumerate() {
+            let existing: Option<i32> = sqlx::query_scalar("SELECT id FROM links WHERE host = $1 AND source = $2")
+                .bind(&link.host)
+                .bind(&link.source)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+            match existing {
+                None => {
+                    let row = sqlx::query(&format!("INSERT INTO links (host, source, target) VALUES ($1, $2, $3) RETURNING {LINK_COLUMNS}"))
+                        .bind(&link.host)
+                        .bind(&link.source)
+                        .bind(&link.target)
+                        .fetch_one(&mut *tx)
+                        .await
+                        .map_err(DbError::conflict_on(&link.host, &link.source))?;
+                    report.created += 1;
+                    written.push(link_from_row(&row));
+                }
+                Some(id) if policy == ConflictPolicy::Overwrite => {
+                    let row = sqlx::query(&format!("UPDATE links SET target = $1, updated_at = {NOW}, version = version + 1 WHERE id = $2 RETURNING {LINK_COLUMNS}"))
+                        .bind(&link.target)
+                        .bind(id)
+                        .fetch_one(&mut *tx)
+                        .await?;
+                    report.updated += 1;
+                    report.conflicts.push(import_conflict(index, link, "overwritten"));
+                    written.push(link_from_row(&row));
+                }
+                Some(_) => {
+                    report.conflicts.push(import_conflict(index, link, "already exists"));
+                    if policy == ConflictPolicy::Fail {
+                        tx.rollback().await?;
+                        report.created = 0;
+                        return Ok((report, Vec::new()));
+                    }
+                    report.skipped += 1;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        report.applied = true;
+
+        Ok((report, written))
+    }
+
+    async fn sync_links(&self, scope: &SyncScope, desired: &[CreateLinkRequest], apply: bool) -> DbResult<(SyncPlan, Vec<Link>)> {
+        // The transaction only takes SQLite's write lock at its first write. If another writer
+        // commits after the scope is read, that write fails with SQLITE_BUSY, so a stale plan
+        // is never applied, but the sync has to be retried
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE host = $1 AND (source = $2 OR substr(source, 1, length($2) + 1) = $2 || '/') ORDER BY source"))
+            .bind(&scope.host)
+            .bind(scope.path_prefix())
+            .fetch_all(&mut *tx)
+            .await?;
+        let existing: Vec<Link> = rows.iter().map(link_from_row).collect();
+
+        let plan = SyncPlan::compute(&existing, desired);
+        if !apply || plan.is_empty() {
+            tx.rollback().await?;
+            return Ok((plan, Vec::new()));
+        }
+
+        for link in &plan.delete {
+            sqlx::query("DELETE FROM links WHERE id = $1")
+                .bind(link.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let mut written = Vec::new();
+        for link in &plan.update {
+            let row = sqlx::query(&format!("UPDATE links SET target = $1, updated_at = {NOW}, version = version + 1 WHERE id = $2 RETURNING {LINK_COLUMNS}"))
+                .bind(&link.new_target)
+                .bind(link.id)
+                .fetch_one(&mut *tx)
+                .await?;
+            written.push(link_from_row(&row));
+        }
+        for link in &plan.create {
+            let row = sqlx::query(&format!("INSERT INTO links (host, source, target) VALUES ($1, $2, $3) RETURNING {LINK_COLUMNS}"))
+                .bind(&link.host)
+                .bind(&link.source)
+                .bind(&link.target)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(DbError::conflict_on(&link.host, &link.source))?;
+            written.push(link_from_row(&row));
+        }
+
+        tx.commit().await?;
+
+        Ok((plan, written))
+    }
+
+    async fn apply_batch(&self, operations: &[BatchOperation]) -> DbResult<(BatchReport, Vec<LinkChange>)> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::new();
+        let mut changes = Vec::new();
+
+        for (index, operation) in operations.iter().enumerate() {
+            // A savepoint per operation, so one failing doesn't stop the rest being tried
+            let mut savepoint = tx.begin().await?;
+            match apply_operation(&mut savepoint, operation).await {
+                Ok(change) => {
+                    savepoint.commit().await?;
+                    results.push(BatchResult::ok(index, &change));
+                    changes.push(change);
+                }
+                Err(e) if batch::is_operation_error(&e) => {
+                    savepoint.rollback().await?;
+                    results.push(BatchResult::failed(index, &e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let report = BatchReport::new(results);
+        if !report.applied {
+            tx.rollback().await?;
+            return Ok((report, Vec::new()));
+        }
+        tx.commit().await?;
+
+        Ok((report, changes))
+    }
+
+    async fn retarget_links(&self, rewrite: &Rewrite, apply: bool) -> DbResult<(Vec<Retarget>, Vec<Link>)> {
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE ($1 IS NULL OR host = $1) AND instr(target, $2) > 0 ORDER BY host, source"))
+            .bind(rewrite.host.as_deref())
+            .bind(rewrite.required_text())
+            .fetch_all(&mut *tx)
+            .await?;
+        let candidates: Vec<Link> = rows.iter().map(link_from_row).collect();
+
+        let changes = rewrite.plan(&candidates);
+        if !apply {
+            tx.rollback().await?;
+            return Ok((changes, Vec::new()));
+        }
+        rewrite.check_preview(&changes)?;
+        if changes.is_empty() {
+            tx.rollback().await?;
+            return Ok((changes, Vec::new()));
+        }
+
+        let reason = rewrite.describe();
+        let mut written = Vec::new();
+        for change in &changes {
+            let row = sqlx::query(&format!("UPDATE links SET target = $1, updated_at = {NOW}, version = version + 1 WHERE id = $2 RETURNING {LINK_COLUMNS}"))
+                .bind(&change.new_target)
+                .bind(change.id)
+                .fetch_one(&mut *tx)
+                .await?;
+            let link = link_from_row(&row);
+
+            sqlx::query("INSERT INTO link_revisions (link_id, version, old_target, new_target, reason) VALUES ($1, $2, $3, $4, $5)")
+                .bind(link.id)
+                .bind(link.version)
+                .bind(&change.old_target)
+                .bind(&change.new_target)
+                .bind(&reason)
+                .execute(&mut *tx)
+                .await?;
+            written.push(link);
+        }
+
+        tx.commit().await?;
+
+        Ok((changes, written))
+    }
+
+    async fn get_revisions(&self, link_id: i32) -> DbResult<Vec<Revision>> {
+        let revisions = sqlx::query_as("SELECT id, link_id, version, old_target, new_target, reason, created_at FROM link_revisions WHERE link_id = $1 ORDER BY id DESC")
+            .bind(link_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(revisions)
+    }
+}
+
+/// Apply one batch operation on the batch's transaction.
+async fn apply_operation(conn: &mut SqliteConnection, operation: &BatchOperation) -> DbResult<LinkChange> {
+    match operation {
+        BatchOperation::Create(request) => {
+            let row = sqlx::query(&format!("INSERT INTO links (host, source, target) VALUES ($1, $2, $3) RETURNING {LINK_COLUMNS}"))
+                .bind(&request.host)
+                .bind(&request.source)
+                .bind(&request.target)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(DbError::conflict_on(&request.host, &request.source))?;
+            Ok(LinkChange { before: None, after: Some(link_from_row(&row)) })
+        }
+        BatchOperation::Update { id, version, changes } => update_in(conn, *id, changes, *version).await,
+        BatchOperation::Delete { id, version } => delete_in(conn, *id, *version).await,
+    }
+}
+
+async fn update_in(conn: &mut SqliteConnection, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+    let existing = find_link(conn, id).await?;
+    check_version(&existing, expected_version)?;
+
+    let host = request.host.as_ref().unwrap_or(&existing.host);
+    let source = request.source.as_ref().unwrap_or(&existing.source);
+    let target = request.target.as_ref().unwrap_or(&existing.target);
+    let row = sqlx::query(&format!("UPDATE links SET host = $1, source = $2, target = $3, updated_at = {NOW}, version = version + 1 WHERE id = $4 RETURNING {LINK_COLUMNS}"))
+        .bind(host)
+        .bind(source)
+        .bind(target)
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(DbError::conflict_on(host, source))?;
+    Ok(LinkChange { before: Some(existing), after: Some(link_from_row(&row)) })
+}
+
+async fn delete_in(conn: &mut SqliteConnection, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+    let existing = find_link(conn, id).await?;
+    check_version(&existing, expected_version)?;
+
+    sqlx::query("DELETE FROM links WHERE id = $1")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(LinkChange { before: Some(existing), after: None })
+}
+
+/// The link with `id`. SQLite has no row locks: if another writer commits between this read
+/// and the transaction's first write, SQLite fails the transaction with `SQLITE_BUSY`
+/// instead of letting it write over the change.
+async fn find_link(conn: &mut SqliteConnection, id: i32) -> DbResult<Link> {
+    let row = sqlx::query(&format!("SELECT {LINK_COLUMNS} FROM links WHERE id = $1"))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    row.as_ref().map(link_from_row).ok_or(DbError::NotFound)
+}
@@ -0,0 +1,138 @@
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::Instrument;
+
+use super::{LinkStore, ListLinksParams, PoolStats, SchemaStatus};
+use crate::batch::{BatchOperation, BatchReport, LinkChange};
+use crate::retarget::{Retarget, Rewrite};
+use crate::error::DbResult;
+use crate::metrics::metrics;
+use crate::sync::{SyncPlan, SyncScope};
+use crate::models::{
+    ConflictPolicy, CreateLinkRequest, ImportReport, Link, LinkPage, Revision, UpdateLinkRequest,
+};
+
+/// Wraps a backend and records how long each call takes, by operation.
+pub(super) struct Timed {
+    inner: Arc<dyn LinkStore>,
+}
+
+impl Timed {
+    pub(super) fn new(inner: Arc<dyn LinkStore>) -> Self {
+        Self { inner }
+    }
+}
+
+async fn timed<T>(operation: &str, call: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let result = call.instrument(tracing::info_span!("db_query", otel.name = operation, operation)).await;
+    metrics()
+        .db_query_duration
+        .with_label_values(&[operation])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+#[async_trait]
+impl LinkStore for Timed {
+    async fn migrate(&self) -> DbResult<()> {
+        timed("migrate", self.inner.migrate()).await
+    }
+
+    async fn schema_status(&self) -> DbResult<SchemaStatus> {
+        timed("schema_status", self.inner.schema_status()).await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.inner.pool_stats()
+    }
+
+    async fn get_all_links(&self) -> DbResult<Vec<Link>> {
+        timed("get_all_links", self.inner.get_all_links()).await
+    }
+
+    // Rows arrive as the caller consumes them, so there's no single duration to record
+    fn stream_links(&self) -> BoxStream<'_, DbResult<Link>> {
+        self.inner.stream_links()
+    }
+
+    async fn get_link_by_id(&self, id: i32) -> DbResult<Option<Link>> {
+        timed("get_link_by_id", self.inner.get_link_by_id(id)).await
+    }
+
+    async fn create_link(&self, request: &CreateLinkRequest) -> DbResult<Link> {
+        timed("create_link", self.inner.create_link(request)).await
+    }
+
+    async fn update_link(&self, id: i32, request: &UpdateLinkRequest, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        timed("update_link", self.inner.update_link(id, request, expected_version)).await
+    }
+
+    async fn delete_link(&self, id: i32, expected_version: Option<i32>) -> DbResult<LinkChange> {
+        timed("delete_link", self.inner.delete_link(id, expected_version)).await
+    }
+
+    async fn search_links(&self, query: &str, page: i32, per_page: i32) -> DbResult<Vec<Link>> {
+        timed("search_links", self.inner.search_links(query, page, per_page)).await
+    }
+
+    async fn get_recent_links(&self, limit: i32) -> DbResult<Vec<Link>> {
+        timed("get_recent_links", self.inner.get_recent_links(limit)).await
+    }
+
+    async fn get_link_by_host_and_source(&self, host: &str, source: &str) -> DbResult<Option<Link>> {
+        timed("get_link_by_host_and_source", self.inner.get_link_by_host_and_source(host, source)).await
+    }
+
+    async fn check_source_conflict(&self, host: &str, source: &str, exclude_id: Option<i32>) -> DbResult<bool> {
+        timed("check_source_conflict", self.inner.check_source_conflict(host, source, exclude_id)).await
+    }
+
+    async fn existing_sources(&self, hosts: &[&str]) -> DbResult<HashSet<(String, String)>> {
+        timed("existing_sources", self.inner.existing_sources(hosts)).await
+    }
+
+    async fn get_links_by_target(&self, target: &str) -> DbResult<Vec<Link>> {
+        timed("get_links_by_target", self.inner.get_links_by_target(target)).await
+    }
+
+    async fn list_links(&self, params: &ListLinksParams) -> DbResult<LinkPage> {
+        timed("list_links", self.inner.list_links(params)).await
+    }
+
+    async fn count_links(&self, host: Option<&str>) -> DbResult<i64> {
+        timed("count_links", self.inner.count_links(host)).await
+    }
+
+    async fn record_hit(&self, id: i32) -> DbResult<()> {
+        timed("record_hit", self.inner.record_hit(id)).await
+    }
+
+    async fn preview_import(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<ImportReport> {
+        timed("preview_import", self.inner.preview_import(links, policy)).await
+    }
+
+    async fn import_links(&self, links: &[CreateLinkRequest], policy: ConflictPolicy) -> DbResult<(ImportReport, Vec<Link>)> {
+        timed("import_links", self.inner.import_links(links, policy)).await
+    }
+
+    async fn sync_links(&self, scope: &SyncScope, desired: &[CreateLinkRequest], apply: bool) -> DbResult<(SyncPlan, Vec<Link>)> {
+        timed("sync_links", self.inner.sync_links(scope, desired, apply)).await
+    }
+
+    async fn apply_batch(&self, operations: &[BatchOperation]) -> DbResult<(BatchReport, Vec<LinkChange>)> {
+        timed("apply_batch", self.inner.apply_batch(operations)).await
+    }
+
+    async fn retarget_links(&self, rewrite: &Rewrite, apply: bool) -> DbResult<(Vec<Retarget>, Vec<Link>)> {
+        timed("retarget_links", self.inner.retarget_links(rewrite, apply)).await
+    }
+
+    async fn get_revisions(&self, link_id: i32) -> DbResult<Vec<Revision>> {
+        timed("get_revisions", self.inner.get_revisions(link_id)).await
+    }
+}
@@ -0,0 +1,136 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use std::fmt;
+
+use crate::models::ErrorResponse;
+
+/// Errors from [`crate::database::Database`], classified so callers can react without
+/// inspecting driver messages.
+#[derive(Debug)]
+pub enum DbError {
+    NotFound,
+    /// A link with this host and source already exists
+    Conflict { host: String, source: String },
+    /// The link has changed since the caller read it
+    Stale,
+    /// The database could not be reached or has no capacity
+    Unavailable(sqlx::Error),
+    Other(anyhow::Error),
+}
+
+pub type DbResult<T> = Result<T, DbError>;
+
+// Postgres SQLSTATE classes
+const CONNECTION_EXCEPTION_CLASS: &str = "08";
+const INSUFFICIENT_RESOURCES_CLASS: &str = "53";
+const OPERATOR_INTERVENTION_CLASS: &str = "57";
+
+impl DbError {
+    /// Map `error`, reporting a unique violation as a conflict on `host` and `source`.
+    pub fn conflict_on(host: &str, source: &str) -> impl FnOnce(sqlx::Error) -> DbError {
+        let (host, source) = (host.to_string(), source.to_string());
+        move |error| match DbError::from(error) {
+            DbError::Other(e) if is_unique_violation(&e) => DbError::Conflict { host, source },
+            other => other,
+        }
+    }
+}
+
+fn is_unique_violation(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(e)) => e.is_unique_violation(),
+        _ => false,
+    }
+}
+
+impl From<sqlx::Error> for DbError {
+    fn from(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => DbError::NotFound,
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => DbError::Unavailable(error),
+            sqlx::Error::Database(e) => {
+                let code = e.code().unwrap_or_default();
+                if code.starts_with(CONNECTION_EXCEPTION_CLASS)
+                    || code.starts_with(INSUFFICIENT_RESOURCES_CLASS)
+                    || code.starts_with(OPERATOR_INTERVENTION_CLASS)
+                {
+                    DbError::Unavailable(error)
+                } else {
+                    DbError::Other(error.into())
+                }
+            }
+            _ => DbError::Other(error.into()),
+        }
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for DbError {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        DbError::Other(error.into())
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::NotFound => write!(f, "Link not found"),
+            DbError::Conflict { host, source } => {
+                write!(f, "A link with host '{}' and source '{}' already exists", host, source)
+            }
+            DbError::Stale => write!(f, "The link has changed since it was read; fetch it again and retry"),
+            DbError::Unavailable(e) => write!(f, "Database unavailable: {}", e),
+            DbError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// An error returned from a JSON API handler, rendered as an [`ErrorResponse`].
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(error: DbError) -> Self {
+        let status = match &error {
+            DbError::NotFound => StatusCode::NOT_FOUND,
+            DbError::Conflict { .. } => StatusCode::CONFLICT,
+            DbError::Stale => StatusCode::PRECONDITION_FAILED,
+            DbError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DbError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(ErrorResponse { error: self.message })).into_response()
+    }
+}
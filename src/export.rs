@@ -0,0 +1,171 @@
+use anyhow::Result;
+
+use crate::models::{ExportFormat, LinkResponse};
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Yaml => "application/yaml",
+            ExportFormat::Bookmarks => "text/html; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Yaml => "yaml",
+            ExportFormat::Bookmarks => "html",
+        }
+    }
+}
+
+const CSV_HEADER: &str = "id,host,source,target,created_at,updated_at,hit_count,version\n";
+
+const BOOKMARKS_HEADER: &str = "<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">
+<TITLE>Lynx Links</TITLE>
+<H1>Lynx Links</H1>
+<DL><p>
+";
+
+/// Text written before the first link.
+pub fn header(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Json => "[",
+        ExportFormat::Csv => CSV_HEADER,
+        ExportFormat::Bookmarks => BOOKMARKS_HEADER,
+        ExportFormat::Ndjson | ExportFormat::Yaml => "",
+    }
+}
+
+/// Text written after the last link.
+pub fn footer(format: ExportFormat, empty: bool) -> &'static str {
+    match format {
+        ExportFormat::Json if empty => "]\n",
+        ExportFormat::Json => "\n]\n",
+        ExportFormat::Bookmarks => "</DL><p>\n",
+        ExportFormat::Ndjson | ExportFormat::Csv => "",
+        // An empty YAML document would read back as null rather than a list
+        ExportFormat::Yaml if empty => "[]\n",
+        ExportFormat::Yaml => "",
+    }
+}
+
+/// Serialize a single link; `first` is set for the first link of the export.
+pub fn record(format: ExportFormat, link: &LinkResponse, first: bool) -> Result<String> {
+    Ok(match format {
+        ExportFormat::Json => {
+            let separator = if first { "\n  " } else { ",\n  " };
+            format!("{}{}", separator, serde_json::to_string(link)?)
+        }
+        ExportFormat::Ndjson => format!("{}\n", serde_json::to_string(link)?),
+        ExportFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+            writer.serialize(link)?;
+            String::from_utf8(writer.into_inner()?)?
+        }
+        ExportFormat::Yaml => serde_yaml::to_string(&[link])?,
+        ExportFormat::Bookmarks => {
+            // Browsers ignore HOST; import reads it back so links keep their host
+            let keyword = link.source.trim_start_matches('/');
+            format!(
+                "    <DT><A HREF=\"{}\" ADD_DATE=\"{}\" SHORTCUTURL=\"{}\" HOST=\"{}\">{}/{}</A>\n",
+                escape_html(&link.target),
+                link.created_at.timestamp(),
+                escape_html(keyword),
+                escape_html(&link.host),
+                escape_html(&link.host),
+                escape_html(keyword),
+            )
+        }
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::parse_links;
+    use crate::models::ImportFormat;
+    use chrono::Utc;
+
+    fn test_links() -> Vec<LinkResponse> {
+        vec![
+            LinkResponse {
+                id: 1,
+                host: "go".to_string(),
+                source: "/docs".to_string(),
+                target: "https://docs.example.com/?a=1&b=2".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                hit_count: 3,
+                version: 1,
+            },
+            LinkResponse {
+                id: 2,
+                host: "t".to_string(),
+                source: "/user/{id}".to_string(),
+                target: "https://example.com/profile?id={id}".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                hit_count: 0,
+                version: 1,
+            },
+        ]
+    }
+
+    fn export(format: ExportFormat, links: &[LinkResponse]) -> String {
+        let mut out = header(format).to_string();
+        for (i, link) in links.iter().enumerate() {
+            out.push_str(&record(format, link, i == 0).unwrap());
+        }
+        out.push_str(footer(format, links.is_empty()));
+        out
+    }
+
+    #[test]
+    fn test_json_export_is_valid() {
+        let out = export(ExportFormat::Json, &test_links());
+        let parsed: Vec<serde_json::Value> = serde_json::from_str(&out).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0]["hit_count"], 3);
+
+        let empty: Vec<serde_json::Value> = serde_json::from_str(&export(ExportFormat::Json, &[])).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_yaml_export_is_a_list() {
+        let parsed: Vec<serde_yaml::Value> = serde_yaml::from_str(&export(ExportFormat::Yaml, &test_links())).unwrap();
+        assert_eq!(parsed.len(), 2);
+
+        let empty: Vec<serde_yaml::Value> = serde_yaml::from_str(&export(ExportFormat::Yaml, &[])).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_exports_round_trip_through_import() {
+        for (export_format, import_format) in [
+            (ExportFormat::Csv, ImportFormat::Csv),
+            (ExportFormat::Json, ImportFormat::Json),
+            (ExportFormat::Bookmarks, ImportFormat::Bookmarks),
+        ] {
+            let links = parse_links(import_format, &export(export_format, &test_links()), "go").unwrap();
+            assert_eq!(links.len(), 2);
+            assert_eq!((links[0].host.as_str(), links[0].source.as_str()), ("go", "/docs"));
+            assert_eq!(links[0].target, "https://docs.example.com/?a=1&b=2");
+            assert_eq!((links[1].host.as_str(), links[1].source.as_str()), ("t", "/user/{id}"));
+        }
+    }
+}
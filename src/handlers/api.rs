@@ -1,253 +1,351 @@
 use axum::{
+    body::{Body, Bytes},
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    http::{header::{CONTENT_DISPOSITION, CONTENT_TYPE, ETAG, IF_MATCH}, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
+use futures::TryStreamExt;
+use serde::Serialize;
 use serde_json::Value;
+use tokio_stream::wrappers::ReceiverStream;
 
 use crate::{
-    models::{CreateLinkRequest, UpdateLinkRequest, LinkResponse, ErrorResponse, SearchQuery},
+    batch::{self, BatchReport, BatchRequest, LinkChange},
+    cache::{self, CacheDrift, CacheOp, CacheStatus},
+    database::ListLinksParams,
+    error::{ApiError, DbError},
+    export,
+    import::{normalize_source, parse_links},
+    models::{CreateLinkRequest, UpdateLinkRequest, LinkResponse, SearchQuery, ListLinksQuery, LinkCursor, ImportQuery, ImportFormat, ImportReport, ExportQuery, SyncQuery, RetargetQuery, ResolveQuery, Revision},
+    redirector::{self, Explanation},
+    retarget::{RetargetReport, RetargetRequest, Rewrite},
+    sync::{Manifest, SyncReport},
     AppState,
 };
 
-pub async fn list_links(State(state): State<AppState>) -> Result<Json<Vec<LinkResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    match state.db.get_recent_links(50).await {
-        Ok(links) => {
-            let responses: Vec<LinkResponse> = links.into_iter().map(LinkResponse::from).collect();
-            Ok(Json(responses))
+pub async fn list_links(
+    Query(params): Query<ListLinksQuery>,
+    State(state): State<AppState>,
+) -> Result<(HeaderMap, Json<Vec<LinkResponse>>), ApiError> {
+    let params = list_params(params, 50).ok_or_else(|| ApiError::bad_request("Invalid 'cursor' query parameter"))?;
+    let page = state.reader().list_links(&params).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("x-total-count", HeaderValue::from(page.total));
+    if let Some(cursor) = page.next_cursor {
+        if let Ok(value) = HeaderValue::from_str(&urlencoding::encode(&cursor.encode())) {
+            headers.insert("x-next-cursor", value);
         }
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to fetch links: {}", e),
-            }),
-        )),
     }
+
+    let responses: Vec<LinkResponse> = page.links.into_iter().map(LinkResponse::from).collect();
+    Ok((headers, Json(responses)))
+}
+
+/// Resolve listing query parameters into database parameters, returning `None` for a malformed cursor.
+pub fn list_params(query: ListLinksQuery, default_limit: i64) -> Option<ListLinksParams> {
+    let sort = query.sort.unwrap_or_default();
+    let cursor = match query.cursor.as_deref().filter(|c| !c.is_empty()) {
+        Some(cursor) => Some(LinkCursor::decode(cursor, sort)?),
+        None => None,
+    };
+
+    Some(ListLinksParams {
+        host: query.host.filter(|h| !h.is_empty()),
+        sort,
+        order: query.order.unwrap_or_else(|| sort.default_order()),
+        cursor,
+        limit: query.limit.unwrap_or(default_limit).clamp(1, 200),
+    })
+}
+
+/// The link's version as a strong `ETag`, to be sent back in `If-Match`.
+fn etag(link: &LinkResponse) -> [(HeaderName, HeaderValue); 1] {
+    [(ETAG, HeaderValue::from_str(&format!("\"{}\"", link.version)).expect("a quoted number is a valid header"))]
+}
+
+/// The version a `PUT` or `DELETE` expects the link to be at, from its required `If-Match`
+/// header. `*` matches any version and gives `None`.
+fn expected_version(headers: &HeaderMap) -> Result<Option<i32>, ApiError> {
+    let value = headers.get(IF_MATCH).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::PRECONDITION_REQUIRED,
+            "The If-Match header is required: send the link's ETag, or * to change it whatever its version",
+        )
+    })?;
+    let value = value.to_str().map_err(|_| ApiError::bad_request("Invalid If-Match header"))?.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    // Anything that isn't one of our ETags can never match
+    value.trim_matches('"').parse().map(Some).map_err(|_| DbError::Stale.into())
 }
 
 pub async fn get_link(
     Path(id): Path<i32>,
     State(state): State<AppState>,
-) -> Result<Json<LinkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state.db.get_link_by_id(id).await {
-        Ok(Some(link)) => Ok(Json(LinkResponse::from(link))),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Link not found".to_string(),
-            }),
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to fetch link: {}", e),
-            }),
-        )),
-    }
+) -> Result<impl IntoResponse, ApiError> {
+    let link = state.reader().get_link_by_id(id).await?.ok_or(DbError::NotFound)?;
+    let response = LinkResponse::from(link);
+    Ok((etag(&response), Json(response)))
 }
 
 pub async fn create_link(
     State(state): State<AppState>,
-    Json(request): Json<CreateLinkRequest>,
-) -> Result<Json<LinkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Check for conflicts
-    if let Ok(has_conflict) = state.db.check_source_conflict(&request.host, &request.source, None).await {
-        if has_conflict {
-            return Err((
-                StatusCode::CONFLICT,
-                Json(ErrorResponse {
-                    error: format!("A link with host '{}' and source '{}' already exists", request.host, request.source),
-                }),
-            ));
-        }
-    }
+    Json(mut request): Json<CreateLinkRequest>,
+) -> Result<Json<LinkResponse>, ApiError> {
+    request.host = state.config.canonical_host(&request.host);
 
-    match state.db.create_link(&request).await {
-        Ok(link) => {
-            // Update cache
-            let mut cache = state.cache.write().await;
-            cache.insert((link.host.clone(), link.source.clone()), link.clone());
-            
-            Ok(Json(LinkResponse::from(link)))
-        }
-        Err(e) => {
-            // Handle unique constraint violation
-            if e.to_string().contains("duplicate key") || e.to_string().contains("unique constraint") {
-                Err((
-                    StatusCode::CONFLICT,
-                    Json(ErrorResponse {
-                        error: format!("A link with host '{}' and source '{}' already exists", request.host, request.source),
-                    }),
-                ))
-            } else {
-                Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse {
-                        error: format!("Failed to create link: {}", e),
-                    }),
-                ))
-            }
-        }
-    }
+    // The unique index is the authority on conflicts, so a concurrent insert of the same
+    // host and source still comes back as a conflict
+    let link = state.db.create_link(&request).await?;
+
+    // Update cache
+    state.cache.insert(link.clone()).await;
+
+    Ok(Json(LinkResponse::from(link)))
 }
 
 pub async fn update_link(
     Path(id): Path<i32>,
     State(state): State<AppState>,
-    Json(request): Json<UpdateLinkRequest>,
-) -> Result<Json<LinkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Get the existing link to check current values
-    let existing = match state.db.get_link_by_id(id).await {
-        Ok(Some(link)) => link,
-        Ok(None) => return Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Link not found".to_string(),
-            }),
-        )),
-        Err(e) => return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to fetch link: {}", e),
-            }),
-        )),
-    };
+    headers: HeaderMap,
+    Json(mut request): Json<UpdateLinkRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let expected_version = expected_version(&headers)?;
+    request.host = request.host.map(|host| state.config.canonical_host(&host));
 
-    // Check for conflicts if host or source is being changed
-    let new_host = request.host.as_ref().unwrap_or(&existing.host);
-    let new_source = request.source.as_ref().unwrap_or(&existing.source);
-    
-    if new_host != &existing.host || new_source != &existing.source {
-        if let Ok(has_conflict) = state.db.check_source_conflict(new_host, new_source, Some(id)).await {
-            if has_conflict {
-                return Err((
-                    StatusCode::CONFLICT,
-                    Json(ErrorResponse {
-                        error: format!("A link with host '{}' and source '{}' already exists", new_host, new_source),
-                    }),
-                ));
-            }
-        }
-    }
+    let change = state.db.update_link(id, &request, expected_version).await?;
+    let updated_link = change.after.clone().ok_or(DbError::NotFound)?;
 
-    match state.db.update_link(id, &request).await {
-        Ok(Some(updated_link)) => {
-            // Update cache - remove old entry and add new one
-            let mut cache = state.cache.write().await;
-            cache.remove(&(existing.host, existing.source));
-            cache.insert((updated_link.host.clone(), updated_link.source.clone()), updated_link.clone());
-            
-            Ok(Json(LinkResponse::from(updated_link)))
-        }
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Link not found".to_string(),
-            }),
-        )),
-        Err(e) => {
-            // Handle unique constraint violation
-            if e.to_string().contains("duplicate key") || e.to_string().contains("unique constraint") {
-                Err((
-                    StatusCode::CONFLICT,
-                    Json(ErrorResponse {
-                        error: format!("A link with host '{}' and source '{}' already exists", new_host, new_source),
-                    }),
-                ))
-            } else {
-                Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse {
-                        error: format!("Failed to update link: {}", e),
-                    }),
-                ))
-            }
-        }
-    }
+    // Update cache - remove old entry and add new one
+    state.cache.apply(change.into_cache_ops().collect()).await;
+
+    let response = LinkResponse::from(updated_link);
+    Ok((etag(&response), Json(response)))
 }
 
 pub async fn delete_link(
     Path(id): Path<i32>,
     State(state): State<AppState>,
-) -> Result<Json<Value>, (StatusCode, Json<ErrorResponse>)> {
-    // Get the link first to remove from cache
-    let existing = match state.db.get_link_by_id(id).await {
-        Ok(Some(link)) => Some(link),
-        Ok(None) => None,
-        Err(_) => None,
-    };
+    headers: HeaderMap,
+) -> Result<Json<Value>, ApiError> {
+    let expected_version = expected_version(&headers)?;
 
-    match state.db.delete_link(id).await {
-        Ok(true) => {
-            // Remove from cache if we found the existing link
-            if let Some(link) = existing {
-                let mut cache = state.cache.write().await;
-                cache.remove(&(link.host, link.source));
-            }
-            
-            Ok(Json(serde_json::json!({"message": "Link deleted successfully"})))
-        }
-        Ok(false) => Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Link not found".to_string(),
-            }),
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to delete link: {}", e),
-            }),
-        )),
-    }
+    let change = state.db.delete_link(id, expected_version).await?;
+    state.cache.apply(change.into_cache_ops().collect()).await;
+
+    Ok(Json(serde_json::json!({"message": "Link deleted successfully"})))
 }
 
 pub async fn search_links(
     Query(params): Query<SearchQuery>,
     State(state): State<AppState>,
-) -> Result<Json<Vec<LinkResponse>>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<Vec<LinkResponse>>, ApiError> {
     let query = params.q.unwrap_or_default();
     let page = params.page.unwrap_or(1).max(1);
-    let per_page = params.per_page.unwrap_or(20).min(100).max(1);
+    let per_page = params.per_page.unwrap_or(20).clamp(1, 100);
 
-    match state.db.search_links(&query, page, per_page).await {
-        Ok(links) => {
-            let responses: Vec<LinkResponse> = links.into_iter().map(LinkResponse::from).collect();
-            Ok(Json(responses))
-        }
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to search links: {}", e),
-            }),
-        )),
-    }
+    let links = state.reader().search_links(&query, page, per_page).await?;
+    Ok(Json(links.into_iter().map(LinkResponse::from).collect()))
 }
 
 pub async fn get_links_by_target(
     Query(params): Query<std::collections::HashMap<String, String>>,
     State(state): State<AppState>,
-) -> Result<Json<Vec<LinkResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    let target = match params.get("target") {
-        Some(t) => t,
-        None => return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Missing 'target' query parameter".to_string(),
-            }),
-        )),
-    };
+) -> Result<Json<Vec<LinkResponse>>, ApiError> {
+    let target = params
+        .get("target")
+        .ok_or_else(|| ApiError::bad_request("Missing 'target' query parameter"))?;
+
+    let links = state.reader().get_links_by_target(target).await?;
+    Ok(Json(links.into_iter().map(LinkResponse::from).collect()))
+}
+
+pub async fn resolve(
+    Query(params): Query<ResolveQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Explanation>, ApiError> {
+    let host = params
+        .host
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| ApiError::bad_request("Missing 'host' query parameter"))?;
+    let path = params.path.unwrap_or_default();
+
+    let site = state.config.site(&host);
+    Ok(Json(redirector::explain(&state.cache.snapshot(), &site, &normalize_source(&path))))
+}
+
+pub async fn import_links(
+    Query(params): Query<ImportQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<(StatusCode, Json<ImportReport>), ApiError> {
+    let format = params.format.or_else(|| {
+        headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(ImportFormat::from_content_type)
+    });
+    let format = format.ok_or_else(|| ApiError::bad_request("Missing 'format' query parameter (csv, json or bookmarks)"))?;
 
-    match state.db.get_links_by_target(target).await {
-        Ok(links) => {
-            let responses: Vec<LinkResponse> = links.into_iter().map(LinkResponse::from).collect();
-            Ok(Json(responses))
+    let default_host = params.host.as_deref().unwrap_or(&state.config.default_redirect_host);
+    let links = parse_links(format, &body, default_host).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let links = state.config.canonical_links(links);
+
+    let policy = params.policy.unwrap_or_default();
+    if params.dry_run.unwrap_or(false) {
+        let report = state.db.preview_import(&links, policy).await?;
+        return Ok((StatusCode::OK, Json(report)));
+    }
+
+    let (report, written) = state.db.import_links(&links, policy).await?;
+    if !report.applied {
+        return Ok((StatusCode::CONFLICT, Json(report)));
+    }
+
+    state.cache.apply(written.into_iter().map(CacheOp::Insert).collect()).await;
+
+    Ok((StatusCode::OK, Json(report)))
+}
+
+pub async fn export_links(
+    Query(params): Query<ExportQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    let format = params.format.unwrap_or_default();
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, std::io::Error>>(64);
+
+    // Rows are serialized as they arrive from the database; the bounded channel applies
+    // backpressure so a slow client never causes the whole table to be buffered.
+    let db = state.reader();
+    tokio::spawn(async move {
+        let to_io = |e: &dyn std::fmt::Display| std::io::Error::other(e.to_string());
+
+        if tx.send(Ok(Bytes::from_static(export::header(format).as_bytes()))).await.is_err() {
+            return;
         }
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to fetch links by target: {}", e),
-            }),
-        )),
+
+        let mut links = db.stream_links();
+        let mut empty = true;
+        loop {
+            let chunk = match links.try_next().await {
+                Ok(Some(link)) => export::record(format, &LinkResponse::from(link), empty)
+                    .map(Bytes::from)
+                    .map_err(|e| to_io(&e)),
+                Ok(None) => break,
+                Err(e) => Err(to_io(&e)),
+            };
+            empty = false;
+
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+
+        let _ = tx.send(Ok(Bytes::from_static(export::footer(format, empty).as_bytes()))).await;
+    });
+
+    let disposition = format!("attachment; filename=\"links.{}\"", format.extension());
+    (
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+pub async fn sync_links(
+    Query(params): Query<SyncQuery>,
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<SyncReport>, ApiError> {
+    let mut manifest = Manifest::parse(&body).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    manifest.map_hosts(|host| state.config.canonical_host(host));
+    let desired = manifest.desired_links().map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let apply = !params.dry_run.unwrap_or(false);
+    let (plan, written) = state.db.sync_links(&manifest.scope, &desired, apply).await?;
+    if apply {
+        let removed = plan.delete.iter().map(|link| CacheOp::Remove {
+            host: link.host.clone(),
+            source: link.source.clone(),
+        });
+        let ops = removed.chain(written.into_iter().map(CacheOp::Insert)).collect();
+        state.cache.apply(ops).await;
+    }
+
+    Ok(Json(SyncReport { applied: apply && !plan.is_empty(), plan }))
+}
+
+pub async fn apply_batch(
+    State(state): State<AppState>,
+    Json(mut request): Json<BatchRequest>,
+) -> Result<(StatusCode, Json<BatchReport>), ApiError> {
+    if request.operations.len() > batch::MAX_OPERATIONS {
+        return Err(ApiError::bad_request(format!(
+            "A batch can hold at most {} operations",
+            batch::MAX_OPERATIONS
+        )));
+    }
+    for operation in &mut request.operations {
+        operation.map_host(|host| state.config.canonical_host(host));
+    }
+
+    let (report, changes) = state.db.apply_batch(&request.operations).await?;
+    if !report.applied {
+        return Ok((StatusCode::CONFLICT, Json(report)));
     }
+
+    // Only a committed batch reaches the cache
+    state.cache.apply(changes.into_iter().flat_map(LinkChange::into_cache_ops).collect()).await;
+
+    Ok((StatusCode::OK, Json(report)))
+}
+
+pub async fn retarget_links(
+    Query(params): Query<RetargetQuery>,
+    State(state): State<AppState>,
+    Json(mut request): Json<RetargetRequest>,
+) -> Result<Json<RetargetReport>, ApiError> {
+    request.host = request.host.filter(|host| !host.is_empty()).map(|host| state.config.canonical_host(&host));
+    let rewrite = Rewrite::new(&request).map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let apply = !params.dry_run.unwrap_or(false);
+    let (changes, written) = state.db.retarget_links(&rewrite, apply).await?;
+    let applied = !written.is_empty();
+    state.cache.apply(written.into_iter().map(CacheOp::Insert).collect()).await;
+
+    Ok(Json(RetargetReport { dry_run: !apply, applied, changes }))
+}
+
+pub async fn get_revisions(
+    Path(id): Path<i32>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Revision>>, ApiError> {
+    let db = state.reader();
+    db.get_link_by_id(id).await?.ok_or(DbError::NotFound)?;
+    Ok(Json(db.get_revisions(id).await?))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStatusResponse {
+    pub entries: usize,
+    #[serde(flatten)]
+    pub status: CacheStatus,
+}
+
+pub async fn cache_status(State(state): State<AppState>) -> Json<CacheStatusResponse> {
+    let entries = state.cache.snapshot().len();
+    let status = state.cache_status.read().await.clone();
+    Json(CacheStatusResponse { entries, status })
+}
+
+pub async fn reload_cache(State(state): State<AppState>) -> Result<Json<CacheDrift>, ApiError> {
+    let drift = cache::reload(&state.db, &state.cache, &state.cache_status).await?;
+    Ok(Json(drift))
 }
@@ -6,11 +6,21 @@ use axum::{
 use serde::Deserialize;
 
 use crate::{
-    models::{CreateLinkRequest, UpdateLinkRequest, LinkResponse, SearchQuery},
-    templates::{HomeTemplate, AddTemplate, EditTemplate, SearchTemplate},
+    cache::CacheOp,
+    error::DbError,
+    handlers::api::list_params,
+    import::{normalize_source, parse_links},
+    models::{CreateLinkRequest, UpdateLinkRequest, LinkResponse, SearchQuery, ListLinksQuery, ImportFormat, ConflictPolicy, EditQuery},
+    redirector,
+    retarget::{PreviewedLink, RetargetReport, RetargetRequest, Rewrite},
+    templates::{HomeTemplate, AddTemplate, EditTemplate, SearchTemplate, ImportTemplate, RetargetTemplate},
     AppState,
 };
 
+const STALE_EDIT: &str = "Someone else changed this link while you were editing it. The form now shows their version; make your changes again and save.";
+const STALE_DELETE: &str = "Someone else changed this link after you opened it. Check the current version below before deleting it.";
+const STALE_RETARGET: &str = "The matching links changed since the preview. Check the changes below before applying them.";
+
 #[derive(Deserialize)]
 pub struct AddFormData {
     pub host: String,
@@ -23,18 +33,72 @@ pub struct EditFormData {
     pub host: String,
     pub source: String,
     pub target: String,
+    /// The version of the link the form was filled in from
+    pub version: i32,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteFormData {
+    pub version: i32,
+}
+
+#[derive(Deserialize)]
+pub struct ImportFormData {
+    pub data: String,
+    pub format: ImportFormat,
+    pub policy: ConflictPolicy,
+    pub host: String,
+    pub dry_run: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RetargetFormData {
+    pub find: String,
+    #[serde(default)]
+    pub replace: String,
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub host: String,
+    /// Set by the apply button; otherwise the form previews
+    pub apply: Option<String>,
+    /// The previewed links as space-separated `id:version` pairs
+    #[serde(default)]
+    pub previewed: String,
 }
 
 #[derive(Deserialize)]
 pub struct AddPageQuery {
     pub source: Option<String>,
+    pub host: Option<String>,
+}
+
+pub async fn home(
+    Query(params): Query<ListLinksQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, (StatusCode, String)> {
+    render_home(&state, params, "").await
 }
 
-pub async fn home(State(state): State<AppState>) -> Result<Response, (StatusCode, String)> {
-    match state.db.get_recent_links(20).await {
-        Ok(links) => {
-            let responses: Vec<LinkResponse> = links.into_iter().map(LinkResponse::from).collect();
-            let template = HomeTemplate { links: responses };
+/// Links on the page are prefixed with `admin_url`, so it still works when served from
+/// another host; empty means relative links.
+async fn render_home(state: &AppState, params: ListLinksQuery, admin_url: &str) -> Result<Response, (StatusCode, String)> {
+    let params = list_params(params, 20)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Invalid page cursor".to_string()))?;
+
+    match state.reader().list_links(&params).await {
+        Ok(page) => {
+            let responses: Vec<LinkResponse> = page.links.into_iter().map(LinkResponse::from).collect();
+            let template = HomeTemplate {
+                links: responses,
+                total: page.total,
+                host: params.host.as_deref().unwrap_or(""),
+                sort: params.sort.as_str(),
+                order: params.order.as_str(),
+                is_first_page: params.cursor.is_none(),
+                next_cursor: page.next_cursor.map(|c| c.encode()),
+                admin_url,
+                read_only: state.health.is_read_only(),
+            };
             Ok(template.into_response())
         }
         Err(e) => Err((
@@ -49,10 +113,13 @@ pub async fn add_page(
     State(state): State<AppState>,
 ) -> Response {
     let source = params.source.as_deref().unwrap_or("");
+    let host = params.host.as_deref().unwrap_or(&state.config.default_redirect_host);
     let template = AddTemplate { 
         source, 
+        host,
         error: None,
         default_redirect_host: &state.config.default_redirect_host,
+        read_only: state.health.is_read_only(),
     };
     template.into_response()
 }
@@ -62,51 +129,30 @@ pub async fn add_link(
     Form(form_data): Form<AddFormData>,
 ) -> Response {
     let request = CreateLinkRequest {
-        host: form_data.host.clone(),
+        host: state.config.canonical_host(&form_data.host),
         source: form_data.source.clone(),
         target: form_data.target,
     };
 
-    // Check for conflicts
-    match state.db.check_source_conflict(&request.host, &request.source, None).await {
-        Ok(true) => {
-            let template = AddTemplate {
-                source: &form_data.source,
-                error: Some("A link with this host and source already exists"),
-                default_redirect_host: &state.config.default_redirect_host,
-            };
-            return template.into_response();
-        }
-        Err(_e) => {
-            let template = AddTemplate {
-                source: &form_data.source,
-                error: Some("Failed to check for conflicts"),
-                default_redirect_host: &state.config.default_redirect_host,
-            };
-            return template.into_response();
-        }
-        Ok(false) => {}
-    }
-
     match state.db.create_link(&request).await {
         Ok(link) => {
             // Update cache
-            let mut cache = state.cache.write().await;
-            cache.insert((link.host.clone(), link.source.clone()), link);
+            state.cache.insert(link).await;
             
             Redirect::to("/").into_response()
         }
         Err(e) => {
-            let error_msg = if e.to_string().contains("duplicate key") || e.to_string().contains("unique constraint") {
-                "A link with this host and source already exists"
-            } else {
-                "Failed to create link"
+            let error_msg = match e {
+                DbError::Conflict { .. } => "A link with this host and source already exists",
+                _ => "Failed to create link",
             };
             
             let template = AddTemplate {
                 source: &form_data.source,
+                host: &form_data.host,
                 error: Some(error_msg),
                 default_redirect_host: &state.config.default_redirect_host,
+                read_only: state.health.is_read_only(),
             };
             template.into_response()
         }
@@ -115,14 +161,22 @@ pub async fn add_link(
 
 pub async fn edit_page(
     Path(id): Path<i32>,
+    Query(params): Query<EditQuery>,
     State(state): State<AppState>,
 ) -> Result<Response, (StatusCode, String)> {
-    match state.db.get_link_by_id(id).await {
+    match state.reader().get_link_by_id(id).await {
         Ok(Some(link)) => {
+            // Resolve the tested path against the redirect cache, exactly as a visit would
+            let test = params.test.filter(|path| !path.is_empty()).map(|path| {
+                redirector::explain(&state.cache.snapshot(), &state.config.site(&link.host), &normalize_source(&path))
+            });
             let response = LinkResponse::from(link);
             let template = EditTemplate { 
                 link: &response, 
-                error: None 
+                error: None,
+                admin_url: "",
+                read_only: state.health.is_read_only(),
+                test,
             };
             Ok(template.into_response())
         }
@@ -139,66 +193,47 @@ pub async fn edit_link(
     State(state): State<AppState>,
     Form(form_data): Form<EditFormData>,
 ) -> Response {
-    // Get the existing link
-    let existing = match state.db.get_link_by_id(id).await {
-        Ok(Some(link)) => link,
-        Ok(None) => return (StatusCode::NOT_FOUND, "Link not found").into_response(),
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load link").into_response(),
-    };
-
     let request = UpdateLinkRequest {
-        host: Some(form_data.host.clone()),
+        host: Some(state.config.canonical_host(&form_data.host)),
         source: Some(form_data.source.clone()),
         target: Some(form_data.target),
     };
 
-    // Check for conflicts if host or source changed
-    if form_data.host != existing.host || form_data.source != existing.source {
-        match state.db.check_source_conflict(&form_data.host, &form_data.source, Some(id)).await {
-            Ok(true) => {
-                let response = LinkResponse::from(existing);
-                let template = EditTemplate {
-                    link: &response,
-                    error: Some("A link with this host and source already exists"),
-                };
-                return template.into_response();
-            }
-            Err(_) => {
-                let response = LinkResponse::from(existing);
-                let template = EditTemplate {
-                    link: &response,
-                    error: Some("Failed to check for conflicts"),
-                };
-                return template.into_response();
-            }
-            Ok(false) => {}
-        }
-    }
-
-    match state.db.update_link(id, &request).await {
-        Ok(Some(updated_link)) => {
+    match state.db.update_link(id, &request, Some(form_data.version)).await {
+        Ok(change) => {
             // Update cache
-            let mut cache = state.cache.write().await;
-            cache.remove(&(existing.host, existing.source));
-            cache.insert((updated_link.host.clone(), updated_link.source.clone()), updated_link);
+            state.cache.apply(change.into_cache_ops().collect()).await;
             
             Redirect::to("/").into_response()
         }
-        Ok(None) => (StatusCode::NOT_FOUND, "Link not found").into_response(),
+        Err(DbError::NotFound) => (StatusCode::NOT_FOUND, "Link not found").into_response(),
         Err(e) => {
-            let error_msg = if e.to_string().contains("duplicate key") || e.to_string().contains("unique constraint") {
-                "A link with this host and source already exists"
-            } else {
-                "Failed to update link"
+            let error_msg = match e {
+                DbError::Conflict { .. } => "A link with this host and source already exists",
+                DbError::Stale => STALE_EDIT,
+                _ => "Failed to update link",
             };
-            
-            let response = LinkResponse::from(existing);
-            let template = EditTemplate {
+            edit_page_with_error(&state, id, error_msg).await
+        }
+    }
+}
+
+/// The edit page refilled from the link as it is now, so a retry starts from the current version.
+async fn edit_page_with_error(state: &AppState, id: i32, error: &str) -> Response {
+    match state.db.get_link_by_id(id).await {
+        Ok(Some(link)) => {
+            let response = LinkResponse::from(link);
+            EditTemplate {
                 link: &response,
-                error: Some(error_msg),
-            };
-            template.into_response()
+                error: Some(error),
+                admin_url: "",
+                read_only: state.health.is_read_only(),
+                test: None,
+            }
+            .into_response()
         }
+        Ok(None) => (StatusCode::NOT_FOUND, "Link not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load link").into_response(),
     }
 }
 
@@ -213,7 +248,7 @@ pub async fn search_page(
     let links = if query.is_empty() {
         Vec::new()
     } else {
-        match state.db.search_links(query, page, per_page).await {
+        match state.reader().search_links(query, page, per_page).await {
             Ok(links) => links.into_iter().map(LinkResponse::from).collect(),
             Err(e) => return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -226,6 +261,7 @@ pub async fn search_page(
         query,
         links,
         page,
+        read_only: state.health.is_read_only(),
     };
     
     Ok(template.into_response())
@@ -234,61 +270,176 @@ pub async fn search_page(
 pub async fn delete_link(
     Path(id): Path<i32>,
     State(state): State<AppState>,
+    Form(form_data): Form<DeleteFormData>,
+) -> Response {
+    match state.db.delete_link(id, Some(form_data.version)).await {
+        Ok(change) => {
+            state.cache.apply(change.into_cache_ops().collect()).await;
+            Redirect::to("/").into_response()
+        }
+        Err(DbError::NotFound) => (StatusCode::NOT_FOUND, "Link not found").into_response(),
+        // Back to the edit page, showing the link as it is now
+        Err(DbError::Stale) => edit_page_with_error(&state, id, STALE_DELETE).await,
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete link").into_response(),
+    }
+}
+
+pub async fn import_page(State(state): State<AppState>) -> Response {
+    let template = ImportTemplate {
+        data: "",
+        format: ImportFormat::Csv.as_str(),
+        policy: ConflictPolicy::default().as_str(),
+        host: &state.config.default_redirect_host,
+        error: None,
+        report: None,
+        read_only: state.health.is_read_only(),
+    };
+    template.into_response()
+}
+
+pub async fn import_links(
+    State(state): State<AppState>,
+    Form(form_data): Form<ImportFormData>,
 ) -> Response {
-    // Get the link first to remove from cache
-    let existing = match state.db.get_link_by_id(id).await {
-        Ok(Some(link)) => Some(link),
-        Ok(None) => None,
-        Err(_) => None,
+    let render = |error: Option<&str>, report| {
+        ImportTemplate {
+            data: &form_data.data,
+            format: form_data.format.as_str(),
+            policy: form_data.policy.as_str(),
+            host: &form_data.host,
+            error,
+            report,
+            read_only: state.health.is_read_only(),
+        }
+        .into_response()
     };
 
-    match state.db.delete_link(id).await {
-        Ok(true) => {
-            // Remove from cache if we found the existing link
-            if let Some(link) = existing {
-                let mut cache = state.cache.write().await;
-                cache.remove(&(link.host, link.source));
+    let links = match parse_links(form_data.format, &form_data.data, &form_data.host) {
+        Ok(links) => state.config.canonical_links(links),
+        Err(e) => return render(Some(&e.to_string()), None),
+    };
+
+    if form_data.dry_run.is_some() {
+        return match state.db.preview_import(&links, form_data.policy).await {
+            Ok(report) => render(None, Some(report)),
+            Err(_) => render(Some("Failed to check import"), None),
+        };
+    }
+
+    match state.db.import_links(&links, form_data.policy).await {
+        Ok((report, written)) => {
+            if report.applied {
+                state.cache.apply(written.into_iter().map(CacheOp::Insert).collect()).await;
+                render(None, Some(report))
+            } else {
+                render(Some("Import aborted: a link conflicts with an existing one"), Some(report))
             }
-            
-            Redirect::to("/").into_response()
         }
-        Ok(false) => (StatusCode::NOT_FOUND, "Link not found").into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete link").into_response(),
+        Err(_) => render(Some("Failed to import links"), None),
     }
 }
 
-// Admin handlers for default hostname (e.g., go/admin)
-pub async fn admin_home(State(state): State<AppState>) -> Result<Response, (StatusCode, String)> {
-    match state.db.get_recent_links(20).await {
-        Ok(links) => {
-            let responses: Vec<LinkResponse> = links.into_iter().map(LinkResponse::from).collect();
-            let template = HomeTemplate { links: responses };
-            Ok(template.into_response())
+pub async fn retarget_page(State(state): State<AppState>) -> Response {
+    let template = RetargetTemplate {
+        find: "",
+        replace: "",
+        regex: false,
+        host: "",
+        error: None,
+        report: None,
+        read_only: state.health.is_read_only(),
+    };
+    template.into_response()
+}
+
+pub async fn retarget_links(
+    State(state): State<AppState>,
+    Form(form_data): Form<RetargetFormData>,
+) -> Response {
+    let render = |error: Option<&str>, report| {
+        RetargetTemplate {
+            find: &form_data.find,
+            replace: &form_data.replace,
+            regex: form_data.regex.is_some(),
+            host: &form_data.host,
+            error,
+            report,
+            read_only: state.health.is_read_only(),
         }
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to load links: {}", e),
-        )),
+        .into_response()
+    };
+
+    let host = form_data.host.trim();
+    let request = RetargetRequest {
+        find: form_data.find.clone(),
+        replace: form_data.replace.clone(),
+        regex: form_data.regex.is_some(),
+        host: (!host.is_empty()).then(|| state.config.canonical_host(host)),
+        previewed: form_data.apply.as_ref().map(|_| parse_previewed(&form_data.previewed)),
+    };
+    let rewrite = match Rewrite::new(&request) {
+        Ok(rewrite) => rewrite,
+        Err(e) => return render(Some(&e.to_string()), None),
+    };
+
+    let apply = form_data.apply.is_some();
+    match state.db.retarget_links(&rewrite, apply).await {
+        Ok((changes, written)) => {
+            let applied = !written.is_empty();
+            state.cache.apply(written.into_iter().map(CacheOp::Insert).collect()).await;
+            render(None, Some(RetargetReport { dry_run: !apply, applied, changes }))
+        }
+        Err(DbError::Stale) => match state.db.retarget_links(&rewrite, false).await {
+            Ok((changes, _)) => render(Some(STALE_RETARGET), Some(RetargetReport { dry_run: true, applied: false, changes })),
+            Err(_) => render(Some("Failed to retarget links"), None),
+        },
+        Err(_) => render(Some("Failed to retarget links"), None),
     }
 }
 
+/// Parse the form's `id:version` pairs, skipping any that are malformed.
+fn parse_previewed(value: &str) -> Vec<PreviewedLink> {
+    value
+        .split_whitespace()
+        .filter_map(|pair| {
+            let (id, version) = pair.split_once(':')?;
+            Some(PreviewedLink { id: id.parse().ok()?, version: version.parse().ok()? })
+        })
+        .collect()
+}
+
+// Admin handlers for default hostname (e.g., go/admin)
+pub async fn admin_home(
+    Query(params): Query<ListLinksQuery>,
+    admin_url: &str,
+    state: &AppState,
+) -> Result<Response, (StatusCode, String)> {
+    render_home(state, params, admin_url).await
+}
+
+/// Edit page for the link `name` on `host`, served at `<host>/admin/<name>`.
 pub async fn admin_edit_by_name(
-    Path(name): Path<String>,
-    State(state): State<AppState>,
+    host: &str,
+    name: &str,
+    admin_url: &str,
+    state: &AppState,
 ) -> Result<Response, (StatusCode, String)> {
     // Try to find the link by host and source name
     let source = if name.starts_with('/') {
-        name
+        name.to_string()
     } else {
         format!("/{}", name)
     };
     
-    match state.db.get_link_by_host_and_source(&state.config.default_redirect_host, &source).await {
+    match state.reader().get_link_by_host_and_source(host, &source).await {
         Ok(Some(link)) => {
             let response = LinkResponse::from(link);
             let template = EditTemplate { 
                 link: &response, 
-                error: None 
+                error: None,
+                admin_url,
+                read_only: state.health.is_read_only(),
+                test: None,
             };
             Ok(template.into_response())
         }
@@ -0,0 +1,117 @@
+use axum::{extract::State, http::StatusCode, response::Json};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::{cache, error::DbError, AppState};
+
+const DATABASE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Process-wide health flags, cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    draining: Arc<AtomicBool>,
+    read_only: Arc<AtomicBool>,
+}
+
+impl Health {
+    /// Mark the server as shutting down, so readiness fails while connections drain.
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Enter or leave read-only mode, where redirects and admin pages are served from the
+    /// cache and changes are refused because the database can't be reached.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::SeqCst);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+}
+
+/// Check the database every few seconds, switching to read-only mode while it's unreachable.
+/// Once it's back the cache is reloaded, picking up anything changed in the meantime, before
+/// changes are accepted again.
+pub fn spawn_database_monitor(state: AppState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(DATABASE_CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let reachable = !matches!(state.db.schema_status().await, Err(DbError::Unavailable(_)));
+
+            match (state.health.is_read_only(), reachable) {
+                (false, false) => {
+                    tracing::warn!("Database unavailable, serving read-only from the link cache");
+                    state.health.set_read_only(true);
+                }
+                (true, true) => match cache::reload(&state.db, &state.cache, &state.cache_status).await {
+                    Ok(_) => {
+                        tracing::info!("Database is back, leaving read-only mode");
+                        state.health.set_read_only(false);
+                        state.cache.release_store();
+                    }
+                    Err(e) => tracing::warn!("Database is back but the link cache couldn't be reloaded: {}", e),
+                },
+                _ => {}
+            }
+        }
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub draining: bool,
+    /// Serving from the link cache because the database is unavailable
+    pub read_only: bool,
+    /// `ok`, or why the database couldn't be checked
+    pub database: String,
+    pub cache_loaded: bool,
+    pub pending_migrations: Option<usize>,
+}
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz() -> Json<serde_json::Value> {
+    Json(serde_json::json!({"status": "ok"}))
+}
+
+/// Readiness: the cache has been loaded, the server isn't shutting down, and either the
+/// database is reachable with current migrations or we're serving read-only without it.
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let draining = state.health.is_draining();
+    let read_only = state.health.is_read_only();
+    let cache_loaded = state.cache_status.read().await.last_reload.is_some();
+    // The database monitor already knows the database is down; waiting on it here would
+    // only time the probe out
+    let (database, pending_migrations) = if read_only {
+        ("unavailable".to_string(), None)
+    } else {
+        match state.db.schema_status().await {
+            Ok(status) => ("ok".to_string(), Some(status.pending)),
+            Err(e) => (e.to_string(), None),
+        }
+    };
+
+    let ready = !draining && cache_loaded && (read_only || pending_migrations == Some(0));
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (
+        status,
+        Json(Readiness {
+            ready,
+            draining,
+            read_only,
+            database,
+            cache_loaded,
+            pending_migrations,
+        }),
+    )
+}
@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::models::{CreateLinkRequest, ImportFormat};
+
+/// A single imported row; `host` falls back to the import's default host when missing.
+#[derive(Debug, Deserialize)]
+struct ImportRecord {
+    host: Option<String>,
+    source: String,
+    target: String,
+}
+
+pub fn parse_links(format: ImportFormat, input: &str, default_host: &str) -> Result<Vec<CreateLinkRequest>> {
+    let records = match format {
+        ImportFormat::Csv => parse_csv(input)?,
+        ImportFormat::Json => parse_json(input)?,
+        ImportFormat::Bookmarks => parse_bookmarks(input),
+    };
+
+    records
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let source = record.source.trim();
+            let target = record.target.trim();
+            if source.is_empty() || target.is_empty() {
+                return Err(anyhow!("Entry {}: source and target are required", index + 1));
+            }
+
+            let host = record
+                .host
+                .map(|h| h.trim().to_string())
+                .filter(|h| !h.is_empty())
+                .unwrap_or_else(|| default_host.to_string());
+
+            Ok(CreateLinkRequest {
+                host,
+                source: normalize_source(source),
+                target: target.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Sources are stored with a leading slash, which is easy to leave off when writing one.
+pub(crate) fn normalize_source(source: &str) -> String {
+    if source.starts_with('/') {
+        source.to_string()
+    } else {
+        format!("/{}", source)
+    }
+}
+
+fn parse_csv(input: &str) -> Result<Vec<ImportRecord>> {
+    let mut reader = csv::Reader::from_reader(input.as_bytes());
+    let mut records = Vec::new();
+    for result in reader.deserialize() {
+        let record: ImportRecord = result.map_err(|e| anyhow!("Invalid CSV: {}", e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn parse_json(input: &str) -> Result<Vec<ImportRecord>> {
+    serde_json::from_str(input).map_err(|e| anyhow!("Invalid JSON: {}", e))
+}
+
+/// Parse a Netscape bookmark file. The Firefox keyword (`SHORTCUTURL`) is used as the
+/// source when present, otherwise the bookmark title. A `HOST` attribute, written by Lynx's
+/// own export, sets the host.
+fn parse_bookmarks(input: &str) -> Vec<ImportRecord> {
+    let lower = input.to_ascii_lowercase();
+    let mut records = Vec::new();
+    let mut pos = 0;
+
+    while let Some(start) = lower[pos..].find("<a ").map(|i| pos + i) {
+        let Some(tag_end) = lower[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        let close = lower[tag_end..].find("</a>").map(|i| tag_end + i).unwrap_or(input.len());
+
+        let tag = &input[start..tag_end];
+        let title = decode_entities(input[tag_end + 1..close].trim());
+        pos = close;
+
+        let Some(href) = attribute(tag, "href") else {
+            continue;
+        };
+        let source = attribute(tag, "shortcuturl").unwrap_or(title);
+        if source.is_empty() {
+            continue;
+        }
+
+        records.push(ImportRecord {
+            host: attribute(tag, "host"),
+            source,
+            target: href,
+        });
+    }
+
+    records
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let needle = format!("{}=\"", name);
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(&needle).map(|i| search + i) {
+        // Make sure we matched a whole attribute name, not the tail of another one
+        let preceded_by_space = lower[..found].ends_with(|c: char| c.is_ascii_whitespace());
+        let value_start = found + needle.len();
+        if preceded_by_space {
+            let value_end = tag[value_start..].find('"').map(|i| value_start + i)?;
+            return Some(decode_entities(&tag[value_start..value_end]));
+        }
+        search = value_start;
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_with_default_host() {
+        let input = "host,source,target\ngo,/docs,https://docs.example.com\n,wiki,https://wiki.example.com\n";
+        let links = parse_links(ImportFormat::Csv, input, "go").unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[1].host, "go");
+        assert_eq!(links[1].source, "/wiki");
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let input = r#"[{"source": "/docs", "target": "https://docs.example.com", "id": 4}]"#;
+        let links = parse_links(ImportFormat::Json, input, "go").unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].host, "go");
+        assert_eq!(links[0].target, "https://docs.example.com");
+    }
+
+    #[test]
+    fn test_parse_bookmarks() {
+        let input = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><A HREF="https://docs.example.com/?a=1&amp;b=2" ADD_DATE="1700000000">docs</A>
+    <DT><A HREF="https://wiki.example.com" SHORTCUTURL="wiki">Team Wiki</A>
+</DL><p>"#;
+        let links = parse_links(ImportFormat::Bookmarks, input, "go").unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].source, "/docs");
+        assert_eq!(links[0].target, "https://docs.example.com/?a=1&b=2");
+        assert_eq!(links[1].source, "/wiki");
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_target() {
+        let input = r#"[{"source": "/docs", "target": ""}]"#;
+        assert!(parse_links(ImportFormat::Json, input, "go").is_err());
+    }
+}
@@ -1,33 +1,80 @@
+pub mod batch;
+pub mod cache;
+pub mod cli;
 pub mod config;
 pub mod models;
 pub mod handlers;
+pub mod health;
 pub mod redirector;
+pub mod retarget;
 pub mod database;
+pub mod error;
+pub mod import;
+pub mod listener;
+pub mod metrics;
+pub mod origin;
+pub mod snapshot;
+pub mod export;
+pub mod sync;
+pub mod telemetry;
 pub mod templates;
+pub mod tls;
 
 use axum::{
-    extract::Host,
-    http::Uri,
+    extract::{DefaultBodyLimit, Request, State},
+    http::{Method, StatusCode, Uri},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{get, post, put, delete},
     Router,
 };
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use tower_http::{trace::TraceLayer, services::ServeDir, cors::CorsLayer};
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::TraceLayer,
+};
 
-use config::Config;
-use models::Link;
+use cache::CacheStatusHandle;
+pub use cache::LinkCache;
+use config::{Config, HostConfig};
 use database::Database;
+use error::{ApiError, DbResult};
+use metrics::RouteGroup;
+use origin::ClientOrigin;
+
+/// Imports can carry thousands of links, well past axum's default 2MB body limit.
+const IMPORT_BODY_LIMIT: usize = 32 * 1024 * 1024;
 
-pub type LinkCache = Arc<RwLock<HashMap<(String, String), Link>>>;
+/// Build a cache holding every link in the database.
+pub async fn load_cache(db: &Database) -> DbResult<LinkCache> {
+    let links = db.get_all_links().await?;
+    let cache = links
+        .into_iter()
+        .map(|link| ((link.host.clone(), link.source.clone()), link))
+        .collect();
+    Ok(LinkCache::new(cache))
+}
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
     pub cache: LinkCache,
+    pub cache_status: CacheStatusHandle,
     pub config: Config,
+    pub health: health::Health,
+}
+
+impl AppState {
+    /// Where pages and API reads come from: the database, or in read-only mode a copy of
+    /// the link cache, so listing and searching keep working while the database is down.
+    pub fn reader(&self) -> Database {
+        if self.health.is_read_only() {
+            self.cache.store()
+        } else {
+            self.db.clone()
+        }
+    }
 }
 
 pub fn create_app(state: AppState) -> Router {
@@ -35,11 +82,23 @@ pub fn create_app(state: AppState) -> Router {
     let api_routes = Router::new()
         .route("/api/links", get(handlers::api::list_links))
         .route("/api/links", post(handlers::api::create_link))
+        .route("/api/links/export", get(handlers::api::export_links))
+        .route("/api/links/import", post(handlers::api::import_links).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)))
+        .route("/api/links/sync", post(handlers::api::sync_links).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)))
+        .route("/api/links/batch", post(handlers::api::apply_batch).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)))
+        .route("/api/links/retarget", post(handlers::api::retarget_links))
         .route("/api/links/search", get(handlers::api::search_links))
         .route("/api/links/reverse", get(handlers::api::get_links_by_target))
+        .route("/api/resolve", get(handlers::api::resolve))
         .route("/api/links/:id", get(handlers::api::get_link))
         .route("/api/links/:id", put(handlers::api::update_link))
-        .route("/api/links/:id", delete(handlers::api::delete_link));
+        .route("/api/links/:id", delete(handlers::api::delete_link))
+        .route("/api/links/:id/revisions", get(handlers::api::get_revisions))
+        .route("/api/admin/cache", get(handlers::api::cache_status))
+        .route("/api/admin/cache/reload", post(handlers::api::reload_cache))
+        .route_layer(middleware::from_fn_with_state(state.clone(), refuse_changes_when_read_only))
+        .route_layer(middleware::map_response(|response| async { RouteGroup::Api.tag(response) }))
+        .route_layer(middleware::from_fn_with_state(state.clone(), admin_only));
 
     // UI routes  
     let ui_routes = Router::new()
@@ -49,12 +108,36 @@ pub fn create_app(state: AppState) -> Router {
         .route("/edit/:id", get(handlers::ui::edit_page))
         .route("/edit/:id", post(handlers::ui::edit_link))
         .route("/delete/:id", post(handlers::ui::delete_link))
-        .route("/search", get(handlers::ui::search_page));
+        .route("/search", get(handlers::ui::search_page))
+        .route("/import", get(handlers::ui::import_page))
+        .route("/import", post(handlers::ui::import_links).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)))
+        .route("/retarget", get(handlers::ui::retarget_page))
+        .route("/retarget", post(handlers::ui::retarget_links));
+
+    // Metrics are served with the admin UI unless they have a port of their own
+    let ui_routes = match state.config.metrics_port {
+        Some(_) => ui_routes,
+        None => ui_routes.route("/metrics", get(metrics::export)),
+    }
+    .route_layer(middleware::from_fn_with_state(state.clone(), refuse_changes_when_read_only))
+    .route_layer(middleware::map_response(|response| async { RouteGroup::Ui.tag(response) }))
+    .route_layer(middleware::from_fn_with_state(state.clone(), admin_only));
+
+    // Probes answer on every host, ahead of any link with the same path
+    let probe_routes = Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route_layer(middleware::map_response(|response| async { RouteGroup::Probe.tag(response) }));
+
+    let static_routes = Router::new()
+        .nest_service("/static", ServeDir::new("static"))
+        .layer(middleware::map_response(|response| async { RouteGroup::Static.tag(response) }));
 
     Router::new()
         .merge(api_routes)
         .merge(ui_routes)
-        .nest_service("/static", ServeDir::new("static"))
+        .merge(probe_routes)
+        .merge(static_routes)
         .fallback(main_handler)
         .layer(
             CorsLayer::new()
@@ -62,41 +145,106 @@ pub fn create_app(state: AppState) -> Router {
                 .allow_methods(tower_http::cors::Any)
                 .allow_headers(tower_http::cors::Any)
         )
-        .layer(TraceLayer::new_for_http())
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        // Outside the trace layer, so the span can pick up a freshly generated ID
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(middleware::from_fn(metrics::track_requests))
         .with_state(state)
 }
 
+/// Every log line written while handling a request carries its `X-Request-Id`.
+fn request_span(request: &Request) -> tracing::Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        otel.name = %format_args!("{} {}", request.method(), request.uri().path()),
+        otel.kind = "server",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id,
+    );
+    telemetry::set_parent(&span, request.headers());
+    span
+}
+
+/// The `/metrics` endpoint alone, for serving on a separate port.
+pub fn create_metrics_app(state: AppState) -> Router {
+    Router::new().route("/metrics", get(metrics::export)).with_state(state)
+}
+
+/// Admin routes are only served on admin hosts and hosts missing from the config; on
+/// redirect hosts their paths are redirected like any other.
+async fn admin_only(
+    State(state): State<AppState>,
+    origin: ClientOrigin,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.config.host_config(&origin.host) {
+        Some(site) if !site.admin => {
+            let site = site.clone();
+            serve_redirect_host(&site, request.uri(), &origin, state).await
+        }
+        _ => next.run(request).await,
+    }
+}
+
+/// In read-only mode anything that would change links is refused up front, rather than
+/// failing partway through against a database that isn't there.
+async fn refuse_changes_when_read_only(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let safe = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !safe && state.health.is_read_only() {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Lynx is read-only while the database is unavailable; changes can be made once it's back",
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
+
 async fn main_handler(
-    Host(host): Host,
+    origin: ClientOrigin,
     uri: Uri,
-    axum::extract::State(state): axum::extract::State<AppState>,
+    State(state): State<AppState>,
 ) -> Response {
+    match state.config.host_config(&origin.host) {
+        // Admin routes are handled by the router above; this only catches unmatched ones
+        Some(site) if site.admin => handlers::not_found().await,
+        Some(site) => {
+            let site = site.clone();
+            serve_redirect_host(&site, &uri, &origin, state).await
+        }
+        // Hosts missing from the config redirect with the default settings
+        None => {
+            let site = state.config.site(&origin.host);
+            redirector::handle_redirect(&site, uri.path().to_string(), &origin, state).await
+        }
+    }
+}
+
+async fn serve_redirect_host(site: &HostConfig, uri: &Uri, origin: &ClientOrigin, state: AppState) -> Response {
     let path = uri.path();
-    
-    // Route based on host and path
-    if host == state.config.admin_host {
-        // Admin routes are handled by the router above
-        // This fallback should only catch unmatched admin routes
-        handlers::not_found().await
-    } else if host == state.config.default_redirect_host {
-        // Handle admin routes on default hostname (e.g., go/admin)
-        if path == "/admin" {
-            return handlers::ui::admin_home(axum::extract::State(state)).await
+
+    // Admin shortcuts, e.g. go/admin and go/admin/docs. Their pages link back to the admin host.
+    if path == "/admin" {
+        let query = axum::extract::Query::try_from_uri(uri)
+            .unwrap_or_else(|_| axum::extract::Query(Default::default()));
+        let response = handlers::ui::admin_home(query, &origin.admin_url(&state), &state).await
+            .unwrap_or_else(|(status, msg)| (status, msg).into_response());
+        return RouteGroup::Ui.tag(response);
+    } else if let Some(name) = path.strip_prefix("/admin/") {
+        if !name.is_empty() {
+            let response = handlers::ui::admin_edit_by_name(&site.name, name, &origin.admin_url(&state), &state).await
                 .unwrap_or_else(|(status, msg)| (status, msg).into_response());
-        } else if path.starts_with("/admin/") {
-            let name = &path[7..]; // Remove "/admin/" prefix
-            if !name.is_empty() {
-                return handlers::ui::admin_edit_by_name(
-                    axum::extract::Path(name.to_string()),
-                    axum::extract::State(state)
-                ).await.unwrap_or_else(|(status, msg)| (status, msg).into_response());
-            }
+            return RouteGroup::Ui.tag(response);
         }
-        
-        // Regular redirector for all other paths on default hostname
-        redirector::handle_redirect(host, path.to_string(), state).await
-    } else {
-        // Redirector for all other hosts
-        redirector::handle_redirect(host, path.to_string(), state).await
     }
+
+    redirector::handle_redirect(site, path.to_string(), origin, state).await
 }
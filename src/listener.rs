@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::{
+    cache::{self, CacheOp, CacheStatusHandle},
+    database::{Database, PostgresStore},
+    LinkCache,
+};
+
+/// Channel the `links_notify_change` trigger publishes on.
+pub const LINKS_CHANNEL: &str = "lynx_links";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct LinkChange {
+    op: String,
+    id: i32,
+    old_host: Option<String>,
+    old_source: Option<String>,
+}
+
+/// Keep `cache` in step with link changes made by any instance sharing the Postgres
+/// database at `database_url`. Returns straight away for other backends, which can't
+/// notify other instances.
+///
+/// Notifications sent while the listener is disconnected are lost, so the cache is fully
+/// reloaded each time the connection is (re)established.
+pub fn spawn_cache_listener(database_url: String, db: Database, cache: LinkCache, status: CacheStatusHandle) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if !PostgresStore::handles(&database_url) {
+            tracing::info!("Storage backend has no change notifications, relying on periodic cache reloads");
+            return;
+        }
+        loop {
+            if let Err(e) = listen(&database_url, &db, &cache, &status).await {
+                tracing::warn!("Link change listener failed: {}", e);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn listen(database_url: &str, db: &Database, cache: &LinkCache, status: &CacheStatusHandle) -> Result<()> {
+    let mut listener = PostgresStore::listener(database_url).await?;
+    listener.listen(LINKS_CHANNEL).await?;
+    cache::reload(db, cache, status).await?;
+
+    loop {
+        match listener.try_recv().await? {
+            Some(notification) => {
+                match serde_json::from_str::<LinkChange>(notification.payload()) {
+                    Ok(change) => apply_change(db, cache, change).await?,
+                    Err(e) => tracing::warn!("Ignoring malformed link notification: {}", e),
+                }
+            }
+            // The connection dropped. Nothing is listening until a new one is made, so hand
+            // back to the caller to connect and LISTEN again before reloading
+            None => return Err(anyhow!("listener connection lost")),
+        }
+    }
+}
+
+async fn apply_change(db: &Database, cache: &LinkCache, change: LinkChange) -> Result<()> {
+    // Read the current row; the removal and insert land in the cache together
+    let current = match change.op.as_str() {
+        "INSERT" | "UPDATE" => db.get_link_by_id(change.id).await?,
+        _ => None,
+    };
+
+    let mut ops = Vec::new();
+    if let (Some(host), Some(source)) = (change.old_host, change.old_source) {
+        ops.push(CacheOp::Remove { host, source });
+    }
+    if let Some(link) = current {
+        ops.push(CacheOp::Insert(link));
+    }
+    cache.apply(ops).await;
+
+    Ok(())
+}
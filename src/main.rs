@@ -1,54 +1,38 @@
-use lynx::*;
-use lynx::config::Config;
-use lynx::database::Database;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing_subscriber;
+use clap::Parser;
+use lynx::cli::{self, Cli, Command};
+use lynx::config::{Config, LogFormat};
+use lynx::telemetry;
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-
     // Load configuration
     dotenvy::dotenv().ok();
-    let config = Config::from_env()?;
-
-    // Initialize database
-    let db = Database::new(&config.database_url).await?;
-
-    // Initialize cache
-    let cache = Arc::new(RwLock::new(HashMap::new()));
-    
-    // Load links into cache
-    let links = db.get_all_links().await?;
-    {
-        let mut cache_write = cache.write().await;
-        for link in links {
-            cache_write.insert((link.host.clone(), link.source.clone()), link);
-        }
-    }
-
-    let state = AppState {
-        db,
-        cache,
-        config: config.clone(),
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+
+    // Initialize tracing. Logs go to stderr so they never mix with command output on stdout,
+    // like `lynx export` writing a backup.
+    let logs = match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_writer(std::io::stderr).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
     };
+    let provider = config.otlp_endpoint.as_deref().map(telemetry::tracer_provider).transpose()?;
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(logs)
+        .with(provider.as_ref().map(telemetry::layer))
+        .init();
 
-    // Build our application with routes
-    let app = create_app(state);
+    let result = cli::run(cli.command.unwrap_or(Command::Serve), config).await;
 
-    let bind_addr = format!("{}:{}", config.host, config.port);
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    
-    println!("Server running on http://{}", bind_addr);
-    println!("Admin UI: http://{}:{}", config.admin_host, config.port);
-    println!("Redirector: http://{}:{}", config.default_redirect_host, config.port);
-
-    axum::serve(listener, app).await?;
-
-    Ok(())
+    if let Some(provider) = provider {
+        tokio::task::spawn_blocking(move || telemetry::shutdown(provider)).await?;
+    }
+    result
 }
-
-
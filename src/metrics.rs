@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use crate::AppState;
+
+/// Which part of the app answered a request, attached to its response so the request
+/// metrics can be labelled without re-deriving the routing decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    Api,
+    Ui,
+    Redirect,
+    Probe,
+    Static,
+    Metrics,
+}
+
+impl RouteGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Api => "api",
+            RouteGroup::Ui => "ui",
+            RouteGroup::Redirect => "redirect",
+            RouteGroup::Probe => "probe",
+            RouteGroup::Static => "static",
+            RouteGroup::Metrics => "metrics",
+        }
+    }
+
+    /// Tag `response` as answered by this group, unless an inner handler already did.
+    pub fn tag(self, mut response: Response) -> Response {
+        if response.extensions().get::<RouteGroup>().is_none() {
+            response.extensions_mut().insert(self);
+        }
+        response
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_duration: HistogramVec,
+    pub redirects: IntCounterVec,
+    pub cache_entries: IntGaugeVec,
+    pub db_pool_connections: IntGaugeVec,
+    pub db_query_duration: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("lynx".to_string()), None)?;
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route group, method and status"),
+            &["group", "method", "status"],
+        )?;
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route group")
+                .buckets(vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["group"],
+        )?;
+        let redirects = IntCounterVec::new(
+            Opts::new("redirects_total", "Redirect lookups by outcome: exact, prefix, parameter or miss"),
+            &["outcome"],
+        )?;
+        let cache_entries = IntGaugeVec::new(Opts::new("cache_entries", "Links in the redirect cache by host"), &["host"])?;
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state: idle, in_use or max"),
+            &["state"],
+        )?;
+        let db_query_duration = HistogramVec::new(
+            HistogramOpts::new("db_query_duration_seconds", "Database call latency by operation")
+                .buckets(vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0]),
+            &["operation"],
+        )?;
+
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_duration.clone()))?;
+        registry.register(Box::new(redirects.clone()))?;
+        registry.register(Box::new(cache_entries.clone()))?;
+        registry.register(Box::new(db_pool_connections.clone()))?;
+        registry.register(Box::new(db_query_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            http_requests,
+            http_duration,
+            redirects,
+            cache_entries,
+            db_pool_connections,
+            db_query_duration,
+        })
+    }
+}
+
+/// The process-wide metrics.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("metric definitions are valid"))
+}
+
+/// Count and time every request, labelled with the [`RouteGroup`] its response was tagged with.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    let group = response.extensions().get::<RouteGroup>().map_or("other", RouteGroup::as_str);
+    let metrics = metrics();
+    metrics
+        .http_requests
+        .with_label_values(&[group, method.as_str(), response.status().as_str()])
+        .inc();
+    metrics.http_duration.with_label_values(&[group]).observe(started.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Prometheus text exposition. Gauges that describe current state are refreshed per scrape.
+pub async fn export(State(state): State<AppState>) -> Response {
+    let metrics = metrics();
+
+    let mut per_host: HashMap<&str, i64> = HashMap::new();
+    let snapshot = state.cache.snapshot();
+    for (host, _) in snapshot.keys() {
+        *per_host.entry(host.as_str()).or_default() += 1;
+    }
+    // Hosts whose last link went away shouldn't linger at their old count
+    metrics.cache_entries.reset();
+    for (host, count) in per_host {
+        metrics.cache_entries.with_label_values(&[host]).set(count);
+    }
+
+    if let Some(pool) = state.db.pool_stats() {
+        let gauges = &metrics.db_pool_connections;
+        gauges.with_label_values(&["idle"]).set(pool.idle as i64);
+        gauges.with_label_values(&["in_use"]).set(pool.size.saturating_sub(pool.idle as u32) as i64);
+        gauges.with_label_values(&["max"]).set(pool.max as i64);
+    }
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&metrics.registry.gather(), &mut body) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to encode metrics: {}", e)).into_response();
+    }
+
+    RouteGroup::Metrics.tag(([(CONTENT_TYPE, encoder.format_type().to_string())], body).into_response())
+}
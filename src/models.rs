@@ -9,6 +9,25 @@ pub struct Link {
     pub source: String,
     pub target: String,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub hit_count: i64,
+    /// Bumped on every change, and served as the link's `ETag`. Snapshots saved before
+    /// links had versions read back as 0.
+    #[serde(default)]
+    pub version: i32,
+}
+
+/// A link's target as changed by a find-and-replace.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Revision {
+    pub id: i32,
+    pub link_id: i32,
+    /// The link's version after the change
+    pub version: i32,
+    pub old_target: String,
+    pub new_target: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,6 +51,9 @@ pub struct LinkResponse {
     pub source: String,
     pub target: String,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub hit_count: i64,
+    pub version: i32,
 }
 
 impl From<Link> for LinkResponse {
@@ -42,6 +64,9 @@ impl From<Link> for LinkResponse {
             source: link.source,
             target: link.target,
             created_at: link.created_at,
+            updated_at: link.updated_at,
+            hit_count: link.hit_count,
+            version: link.version,
         }
     }
 }
@@ -57,3 +82,227 @@ pub struct SearchQuery {
     pub page: Option<i32>,
     pub per_page: Option<i32>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkSort {
+    #[default]
+    Created,
+    Updated,
+    Source,
+    Hits,
+}
+
+impl LinkSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkSort::Created => "created",
+            LinkSort::Updated => "updated",
+            LinkSort::Source => "source",
+            LinkSort::Hits => "hits",
+        }
+    }
+
+    /// Alphabetical sorts read naturally ascending, everything else newest/most first.
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            LinkSort::Source => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListLinksQuery {
+    pub host: Option<String>,
+    pub sort: Option<LinkSort>,
+    pub order: Option<SortOrder>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Position in a sorted listing: the sort key and id of the last link on the previous page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkCursor {
+    pub id: i32,
+    pub key: CursorKey,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorKey {
+    Time(DateTime<Utc>),
+    Text(String),
+    Count(i64),
+}
+
+impl LinkCursor {
+    pub fn for_link(link: &Link, sort: LinkSort) -> Self {
+        let key = match sort {
+            LinkSort::Created => CursorKey::Time(link.created_at),
+            LinkSort::Updated => CursorKey::Time(link.updated_at),
+            LinkSort::Source => CursorKey::Text(link.source.clone()),
+            LinkSort::Hits => CursorKey::Count(link.hit_count),
+        };
+        Self { id: link.id, key }
+    }
+
+    /// Cursors are encoded as `<id>:<key>`; the id comes first because keys may contain ':'.
+    pub fn encode(&self) -> String {
+        let key = match &self.key {
+            CursorKey::Time(t) => t.to_rfc3339(),
+            CursorKey::Text(s) => s.clone(),
+            CursorKey::Count(n) => n.to_string(),
+        };
+        format!("{}:{}", self.id, key)
+    }
+
+    pub fn decode(cursor: &str, sort: LinkSort) -> Option<Self> {
+        let (id, key) = cursor.split_once(':')?;
+        let id = id.parse().ok()?;
+        let key = match sort {
+            LinkSort::Created | LinkSort::Updated => {
+                CursorKey::Time(DateTime::parse_from_rfc3339(key).ok()?.with_timezone(&Utc))
+            }
+            LinkSort::Source => CursorKey::Text(key.to_string()),
+            LinkSort::Hits => CursorKey::Count(key.parse().ok()?),
+        };
+        Some(Self { id, key })
+    }
+}
+
+#[derive(Debug)]
+pub struct LinkPage {
+    pub links: Vec<Link>,
+    pub next_cursor: Option<LinkCursor>,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Json,
+    Bookmarks,
+}
+
+impl ImportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportFormat::Csv => "csv",
+            ImportFormat::Json => "json",
+            ImportFormat::Bookmarks => "bookmarks",
+        }
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        match mime {
+            "text/csv" => Some(ImportFormat::Csv),
+            "application/json" => Some(ImportFormat::Json),
+            "text/html" => Some(ImportFormat::Bookmarks),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when an imported link's host and source are already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    #[default]
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub format: Option<ImportFormat>,
+    pub host: Option<String>,
+    pub policy: Option<ConflictPolicy>,
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportConflict {
+    pub index: usize,
+    pub host: String,
+    pub source: String,
+    pub target: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Ndjson,
+    Csv,
+    Yaml,
+    Bookmarks,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<ExportFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncQuery {
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetargetQuery {
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveQuery {
+    pub host: Option<String>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditQuery {
+    /// A path to resolve on the link's host, from the edit page's path tester
+    pub test: Option<String>,
+}
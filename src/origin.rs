@@ -0,0 +1,55 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use std::net::SocketAddr;
+
+use crate::AppState;
+
+/// How the client reached us: the host it asked for and, behind a trusted proxy, the scheme
+/// it used. Forwarded headers from anyone else are ignored so they can't choose the host.
+#[derive(Debug, Clone)]
+pub struct ClientOrigin {
+    pub host: String,
+    /// Scheme the proxy received the request over, from `X-Forwarded-Proto`
+    pub proto: Option<String>,
+}
+
+impl ClientOrigin {
+    /// Base URL of the admin UI for links handed to this client.
+    pub fn admin_url(&self, state: &AppState) -> String {
+        state.config.admin_url_for(self.proto.as_deref())
+    }
+}
+
+/// The first value of a possibly comma-separated header, as appended by a chain of proxies.
+fn first_value(parts: &Parts, name: &str) -> Option<String> {
+    let value = parts.headers.get(name)?.to_str().ok()?;
+    let first = value.split(',').next()?.trim();
+    (!first.is_empty()).then(|| first.to_string())
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientOrigin {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let trusted = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .is_some_and(|ConnectInfo(peer)| state.config.trusts_proxy(peer.ip()));
+
+        let forwarded = |name| if trusted { first_value(parts, name) } else { None };
+
+        let host = forwarded("x-forwarded-host")
+            .or_else(|| first_value(parts, "host"))
+            .or_else(|| parts.uri.authority().map(|authority| authority.to_string()))
+            .ok_or((StatusCode::BAD_REQUEST, "No host found in request"))?;
+        let proto = forwarded("x-forwarded-proto")
+            .map(|proto| proto.to_ascii_lowercase())
+            .filter(|proto| proto == "http" || proto == "https");
+
+        Ok(Self { host, proto })
+    }
+}
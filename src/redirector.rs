@@ -1,52 +1,301 @@
 use axum::{
     response::{IntoResponse, Redirect, Response},
 };
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
 
-use crate::{AppState, models::Link};
+use crate::{
+    config::{HostConfig, MissBehaviour},
+    handlers,
+    metrics::{metrics, RouteGroup},
+    models::{Link, LinkResponse},
+    origin::ClientOrigin,
+    AppState,
+};
 
-pub async fn handle_redirect(host: String, path: String, state: AppState) -> Response {
-    // Try to find a matching link in the cache
-    let cache = state.cache.read().await;
-    
+/// How a request path matched a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    /// The path is the link's source
+    Exact,
+    /// The link's source ends in a `{param}` the rest of the path fills in
+    Parameter,
+    /// The link's source is a leading part of the path
+    Prefix,
+}
+
+impl MatchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchKind::Exact => "exact",
+            MatchKind::Parameter => "parameter",
+            MatchKind::Prefix => "prefix",
+        }
+    }
+}
+
+/// The link a path resolved through and where it leads.
+#[derive(Debug, Clone)]
+pub struct Resolution<'a> {
+    pub link: &'a Link,
+    pub target: String,
+    pub kind: MatchKind,
+    /// For a prefix match, the separator the path was last cut at to reach the link's source
+    pub separator: Option<char>,
+    /// For a parameter match, the values the path filled in
+    pub parameters: BTreeMap<String, String>,
+}
+
+/// A source looked up while resolving a path.
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    /// The path tried, or the parameterized source it was matched against
+    pub source: String,
+    pub kind: MatchKind,
+    /// For a prefix, the separator the path was cut at to get it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<char>,
+    /// The link with this source, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_id: Option<i32>,
+    pub matched: bool,
+}
+
+/// Where a path on a host leads and how it got there, for debugging surprising redirects.
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub host: String,
+    pub path: String,
+    /// The redirect's location, or `None` if no link matches
+    pub target: Option<String>,
+    pub link: Option<LinkResponse>,
+    pub kind: Option<MatchKind>,
+    pub separator: Option<char>,
+    pub parameters: BTreeMap<String, String>,
+    /// Every source tried, in the order the redirector tries them
+    pub candidates: Vec<Candidate>,
+}
+
+/// Collects the candidates tried during a resolve, when anyone is asking.
+#[derive(Default)]
+struct Trace<'t>(Option<&'t mut Vec<Candidate>>);
+
+impl Trace<'_> {
+    fn record(&mut self, candidate: impl FnOnce() -> Candidate) {
+        if let Some(candidates) = &mut self.0 {
+            candidates.push(candidate());
+        }
+    }
+}
+
+pub async fn handle_redirect(site: &HostConfig, path: String, origin: &ClientOrigin, state: AppState) -> Response {
+    RouteGroup::Redirect.tag(redirect(site, path, origin, state).await)
+}
+
+async fn redirect(site: &HostConfig, path: String, origin: &ClientOrigin, state: AppState) -> Response {
+    let started = Instant::now();
+
+    // Try to find a matching link in the current cache snapshot
+    let cache = state.cache.snapshot();
+    let lookup = tracing::info_span!("cache_lookup", host = %site.name, path = %path);
+
+    if let Some(resolution) = lookup.in_scope(|| resolve(&cache, &site.name, &path)) {
+        metrics().redirects.with_label_values(&[resolution.kind.as_str()]).inc();
+        record_hit(&state, resolution.link.id);
+        let response = create_redirect_response(&with_scheme(&resolution.target, &site.default_scheme), &path);
+        tracing::info!(
+            target: "lynx::redirect",
+            host = %site.name,
+            path = %path,
+            link_id = resolution.link.id,
+            match_kind = resolution.kind.as_str(),
+            latency_us = started.elapsed().as_micros() as u64,
+            "redirect"
+        );
+        return response;
+    }
+
+    metrics().redirects.with_label_values(&["miss"]).inc();
+    tracing::info!(
+        target: "lynx::redirect",
+        host = %site.name,
+        path = %path,
+        match_kind = "miss",
+        latency_us = started.elapsed().as_micros() as u64,
+        "redirect"
+    );
+    match &site.on_miss {
+        // Redirect to admin add page with source and host prefilled
+        MissBehaviour::AddLink => {
+            let admin_url = format!("{}/add?source={}&host={}",
+                                   origin.admin_url(&state),
+                                   urlencoding::encode(&path),
+                                   urlencoding::encode(&site.name));
+            Redirect::temporary(&admin_url).into_response()
+        }
+        MissBehaviour::NotFound => handlers::not_found().await,
+        MissBehaviour::Redirect(url) => {
+            let path = urlencoding::encode(path.trim_start_matches('/'));
+            Redirect::temporary(&url.replace("{path}", &path)).into_response()
+        }
+    }
+}
+
+/// Targets stored without a scheme (`docs.example.com/x`) get the host's default one.
+fn with_scheme(target: &str, scheme: &str) -> String {
+    if target.contains("://") || target.starts_with('/') {
+        target.to_string()
+    } else {
+        format!("{}://{}", scheme, target)
+    }
+}
+
+/// Find the link a request for `path` on `host` redirects through, and the resulting target.
+pub fn resolve<'a>(
+    cache: &'a HashMap<(String, String), Link>,
+    host: &str,
+    path: &str,
+) -> Option<Resolution<'a>> {
+    resolve_traced(cache, host, path, &mut Trace::default())
+}
+
+/// Resolve `path` on `site` as a redirect would, recording every candidate along the way.
+pub fn explain(cache: &HashMap<(String, String), Link>, site: &HostConfig, path: &str) -> Explanation {
+    let mut candidates = Vec::new();
+    let resolution = resolve_traced(cache, &site.name, path, &mut Trace(Some(&mut candidates)));
+
+    let mut explanation = Explanation {
+        host: site.name.clone(),
+        path: path.to_string(),
+        target: None,
+        link: None,
+        kind: None,
+        separator: None,
+        parameters: BTreeMap::new(),
+        candidates,
+    };
+    if let Some(resolution) = resolution {
+        explanation.target = Some(with_scheme(&resolution.target, &site.default_scheme));
+        explanation.link = Some(LinkResponse::from(resolution.link.clone()));
+        explanation.kind = Some(resolution.kind);
+        explanation.separator = resolution.separator;
+        explanation.parameters = resolution.parameters;
+    }
+    explanation
+}
+
+fn resolve_traced<'a>(
+    cache: &'a HashMap<(String, String), Link>,
+    host: &str,
+    path: &str,
+    trace: &mut Trace,
+) -> Option<Resolution<'a>> {
     // First try exact match
-    if let Some(link) = cache.get(&(host.clone(), path.clone())) {
-        return create_redirect_response(&link.target, &path);
+    let exact = cache.get(&(host.to_string(), path.to_string()));
+    trace.record(|| Candidate {
+        source: path.to_string(),
+        kind: MatchKind::Exact,
+        separator: None,
+        link_id: exact.map(|link| link.id),
+        matched: exact.is_some(),
+    });
+    if let Some(link) = exact {
+        return Some(Resolution {
+            link,
+            target: link.target.clone(),
+            kind: MatchKind::Exact,
+            separator: None,
+            parameters: BTreeMap::new(),
+        });
     }
-    
+
     // Try progressive path splitting
-    if let Some(target) = find_matching_rule(&cache, &host, &path) {
-        return create_redirect_response(&target, &path);
+    find_matching_rule(cache, host, path, trace)
+}
+
+// Count the hit in the background so the redirect isn't held up by a database write.
+// Hits made in read-only mode go uncounted.
+fn record_hit(state: &AppState, id: i32) {
+    if state.health.is_read_only() {
+        return;
     }
-    
-    // No match found, redirect to admin add page with source prefilled
-    let admin_url = format!("http://{}:{}/add?source={}", 
-                           state.config.admin_host,
-                           state.config.port,
-                           urlencoding::encode(&path));
-    
-    Redirect::temporary(&admin_url).into_response()
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        if let Err(e) = db.record_hit(id).await {
+            tracing::warn!("Failed to record hit for link {}: {}", id, e);
+        }
+    });
 }
 
-fn find_matching_rule(
-    cache: &HashMap<(String, String), Link>,
+fn find_matching_rule<'a>(
+    cache: &'a HashMap<(String, String), Link>,
     host: &str,
     path: &str,
-) -> Option<String> {
-    // Try parameterized rules first
-    for ((cache_host, cache_source), link) in cache.iter() {
-        if cache_host == host && is_parameterized_match(cache_source, path) {
-            return Some(substitute_parameters(cache_source, &link.target, path));
+    trace: &mut Trace,
+) -> Option<Resolution<'a>> {
+    // Try parameterized rules first, the most specific (longest prefix) first so the
+    // winner doesn't depend on the map's iteration order
+    let mut rules: Vec<(&str, &String, &Link)> = cache
+        .iter()
+        .filter(|((cache_host, _), _)| cache_host == host)
+        .filter_map(|((_, cache_source), link)| {
+            split_parameter(cache_source).map(|(prefix, _)| (prefix, cache_source, link))
+        })
+        .collect();
+    rules.sort_by(|(a_prefix, a_source, _), (b_prefix, b_source, _)| {
+        b_prefix.len().cmp(&a_prefix.len()).then_with(|| a_source.cmp(b_source))
+    });
+    for (_, cache_source, link) in rules {
+        let matched = is_parameterized_match(cache_source, path);
+        trace.record(|| Candidate {
+            source: cache_source.clone(),
+            kind: MatchKind::Parameter,
+            separator: None,
+            link_id: Some(link.id),
+            matched,
+        });
+        if matched {
+            return Some(Resolution {
+                link,
+                target: substitute_parameters(cache_source, &link.target, path),
+                kind: MatchKind::Parameter,
+                separator: None,
+                parameters: capture_parameter(cache_source, path)
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .into_iter()
+                    .collect(),
+            });
         }
     }
     
     // Try progressive path splitting
     let separators = ['/', '.', '?'];
     let mut current_path = path.to_string();
+    let mut separator = None;
     
     while !current_path.is_empty() {
-        if let Some(link) = cache.get(&(host.to_string(), current_path.clone())) {
-            return Some(link.target.clone());
+        let link = cache.get(&(host.to_string(), current_path.clone()));
+        // The whole path was already tried as an exact match
+        if current_path != path {
+            trace.record(|| Candidate {
+                source: current_path.clone(),
+                kind: MatchKind::Prefix,
+                separator,
+                link_id: link.map(|link| link.id),
+                matched: link.is_some(),
+            });
+        }
+        if let Some(link) = link {
+            let kind = if current_path == path { MatchKind::Exact } else { MatchKind::Prefix };
+            return Some(Resolution {
+                link,
+                target: link.target.clone(),
+                kind,
+                separator,
+                parameters: BTreeMap::new(),
+            });
         }
         
         // Split from the right using separators
@@ -54,6 +303,7 @@ fn find_matching_rule(
         for sep in separators.iter() {
             if let Some(pos) = current_path.rfind(*sep) {
                 current_path = current_path[..pos].to_string();
+                separator = Some(*sep);
                 found_separator = true;
                 break;
             }
@@ -67,31 +317,29 @@ fn find_matching_rule(
     None
 }
 
+/// Split a source ending in `/{param}` into the path before the parameter and its name.
+fn split_parameter(pattern: &str) -> Option<(&str, &str)> {
+    let param_start = pattern.rfind("/{")?;
+    let name = pattern[param_start + 2..].strip_suffix('}')?;
+    Some((&pattern[..param_start + 1], name))
+}
+
+/// The name and value of the parameter `path` fills in, if it matches `pattern`.
+fn capture_parameter<'p>(pattern: &'p str, path: &'p str) -> Option<(&'p str, &'p str)> {
+    let (prefix, name) = split_parameter(pattern)?;
+    let value = path.strip_prefix(prefix).filter(|value| !value.is_empty())?;
+    Some((name, value))
+}
+
 fn is_parameterized_match(pattern: &str, path: &str) -> bool {
-    // Simple parameterized matching: pattern ends with {param}
-    if let Some(param_start) = pattern.rfind("/{") {
-        if pattern.ends_with("}") {
-            let prefix = &pattern[..param_start + 1];
-            return path.starts_with(prefix) && path.len() > prefix.len();
-        }
-    }
-    false
+    capture_parameter(pattern, path).is_some()
 }
 
 fn substitute_parameters(pattern: &str, target: &str, path: &str) -> String {
-    if let Some(param_start) = pattern.rfind("/{") {
-        if pattern.ends_with("}") {
-            let prefix = &pattern[..param_start + 1];
-            if path.starts_with(prefix) {
-                let param_value = &path[prefix.len()..];
-                // Extract parameter name from pattern
-                let param_name = &pattern[param_start + 2..pattern.len() - 1];
-                let param_placeholder = format!("{{{}}}", param_name);
-                return target.replace(&param_placeholder, param_value);
-            }
-        }
+    match capture_parameter(pattern, path) {
+        Some((name, value)) => target.replace(&format!("{{{}}}", name), value),
+        None => target.to_string(),
     }
-    target.to_string()
 }
 
 fn create_redirect_response(target: &str, _path: &str) -> Response {
@@ -112,6 +360,9 @@ mod tests {
             source: source.to_string(),
             target: target.to_string(),
             created_at: Utc::now(),
+            updated_at: Utc::now(),
+            hit_count: 0,
+            version: 1,
         }
     }
 
@@ -121,7 +372,7 @@ mod tests {
         let link = create_test_link(1, "go", "/test", "https://example.com");
         cache.insert(("go".to_string(), "/test".to_string()), link);
         
-        let result = find_matching_rule(&cache, "go", "/test");
+        let result = find_matching_rule(&cache, "go", "/test", &mut Trace::default()).map(|resolution| resolution.target);
         assert_eq!(result, Some("https://example.com".to_string()));
     }
 
@@ -131,18 +382,75 @@ mod tests {
         let link = create_test_link(1, "go", "/user/{id}", "https://example.com/profile?id={id}");
         cache.insert(("go".to_string(), "/user/{id}".to_string()), link);
         
-        let result = find_matching_rule(&cache, "go", "/user/123");
+        let result = find_matching_rule(&cache, "go", "/user/123", &mut Trace::default()).map(|resolution| resolution.target);
         assert_eq!(result, Some("https://example.com/profile?id=123".to_string()));
     }
 
+    #[test]
+    fn test_parameterized_match_prefers_longest_prefix() {
+        let mut cache = HashMap::new();
+        for link in [
+            create_test_link(1, "go", "/api/{path}", "https://api.example.com/{path}"),
+            create_test_link(2, "go", "/api/v1/{endpoint}", "https://v1.example.com/{endpoint}"),
+            create_test_link(3, "go", "/api/{name}", "https://other.example.com/{name}"),
+        ] {
+            cache.insert((link.host.clone(), link.source.clone()), link);
+        }
+
+        let result = find_matching_rule(&cache, "go", "/api/v1/users", &mut Trace::default()).unwrap();
+        assert_eq!(result.target, "https://v1.example.com/users");
+
+        // Rules with the same prefix are tried in source order
+        let mut candidates = Vec::new();
+        let result = find_matching_rule(&cache, "go", "/api/users", &mut Trace(Some(&mut candidates))).unwrap();
+        assert_eq!(result.target, "https://other.example.com/users");
+        let tried: Vec<_> = candidates.iter().map(|candidate| candidate.source.as_str()).collect();
+        assert_eq!(tried, vec!["/api/v1/{endpoint}", "/api/{name}"]);
+    }
+
     #[test]
     fn test_progressive_path_splitting() {
         let mut cache = HashMap::new();
         let link = create_test_link(1, "go", "/docs", "https://example.com/documentation");
         cache.insert(("go".to_string(), "/docs".to_string()), link);
         
-        let result = find_matching_rule(&cache, "go", "/docs/api/v1");
-        assert_eq!(result, Some("https://example.com/documentation".to_string()));
+        let result = find_matching_rule(&cache, "go", "/docs/api/v1", &mut Trace::default()).unwrap();
+        assert_eq!(result.target, "https://example.com/documentation");
+        assert_eq!(result.kind, MatchKind::Prefix);
+        assert_eq!(result.separator, Some('/'));
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut cache = HashMap::new();
+        for link in [
+            create_test_link(1, "go", "/docs", "docs.example.com"),
+            create_test_link(2, "go", "/user/{id}", "https://example.com/profile?id={id}"),
+            create_test_link(3, "t", "/docs/api", "https://t.example.com"),
+        ] {
+            cache.insert((link.host.clone(), link.source.clone()), link);
+        }
+        let site = HostConfig::new("go", false);
+
+        let explanation = explain(&cache, &site, "/docs/api/v1");
+        assert_eq!(explanation.target.as_deref(), Some("http://docs.example.com"));
+        assert_eq!(explanation.link.map(|link| link.id), Some(1));
+        assert_eq!((explanation.kind, explanation.separator), (Some(MatchKind::Prefix), Some('/')));
+        let tried: Vec<_> = explanation.candidates.iter().map(|c| (c.source.as_str(), c.kind, c.matched)).collect();
+        assert_eq!(tried, vec![
+            ("/docs/api/v1", MatchKind::Exact, false),
+            ("/user/{id}", MatchKind::Parameter, false),
+            ("/docs/api", MatchKind::Prefix, false),
+            ("/docs", MatchKind::Prefix, true),
+        ]);
+
+        let explanation = explain(&cache, &site, "/user/42");
+        assert_eq!(explanation.kind, Some(MatchKind::Parameter));
+        assert_eq!(explanation.parameters.get("id").map(String::as_str), Some("42"));
+
+        let explanation = explain(&cache, &site, "/missing");
+        assert!(explanation.target.is_none() && explanation.link.is_none());
+        assert_eq!(explanation.candidates.len(), 2);
     }
 
     #[test]
@@ -161,4 +469,11 @@ mod tests {
         let result = substitute_parameters("/api/{version}", "https://api.example.com/{version}/data", "/api/v2");
         assert_eq!(result, "https://api.example.com/v2/data");
     }
+
+    #[test]
+    fn test_with_scheme() {
+        assert_eq!(with_scheme("docs.example.com/api", "https"), "https://docs.example.com/api");
+        assert_eq!(with_scheme("http://example.com", "https"), "http://example.com");
+        assert_eq!(with_scheme("/local", "https"), "/local");
+    }
 }
@@ -0,0 +1,244 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::error::{DbError, DbResult};
+use crate::models::Link;
+
+/// A find-and-replace over link targets, e.g. to follow a wiki to its new domain.
+#[derive(Debug, Deserialize)]
+pub struct RetargetRequest {
+    pub find: String,
+    #[serde(default)]
+    pub replace: String,
+    /// Treat `find` as a regular expression; `replace` can then refer to groups as `$1`
+    #[serde(default)]
+    pub regex: bool,
+    /// Only retarget links on this host
+    pub host: Option<String>,
+    /// The links a preview showed. Applying then fails as stale unless the rewrite still
+    /// changes exactly these links, at these versions.
+    #[serde(default)]
+    pub previewed: Option<Vec<PreviewedLink>>,
+}
+
+/// A link as a preview showed it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub struct PreviewedLink {
+    pub id: i32,
+    pub version: i32,
+}
+
+/// A checked [`RetargetRequest`], ready to run against targets.
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    pattern: Pattern,
+    replace: String,
+    pub host: Option<String>,
+    previewed: Option<HashSet<PreviewedLink>>,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Text(String),
+    Regex(Regex),
+}
+
+/// One link whose target the rewrite changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Retarget {
+    pub id: i32,
+    pub host: String,
+    pub source: String,
+    /// The version the change applies to
+    pub version: i32,
+    pub old_target: String,
+    pub new_target: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetargetReport {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub changes: Vec<Retarget>,
+}
+
+impl RetargetReport {
+    /// The changes as space-separated `id:version` pairs, for the UI to send back on apply
+    pub fn previewed(&self) -> String {
+        self.changes.iter().map(|change| format!("{}:{}", change.id, change.version)).collect::<Vec<_>>().join(" ")
+    }
+}
+
+impl Rewrite {
+    pub fn new(request: &RetargetRequest) -> Result<Self> {
+        if request.find.is_empty() {
+            return Err(anyhow!("Nothing to find"));
+        }
+        let pattern = if request.regex {
+            Pattern::Regex(Regex::new(&request.find).map_err(|e| anyhow!("Invalid regex: {}", e))?)
+        } else {
+            Pattern::Text(request.find.clone())
+        };
+
+        Ok(Self {
+            pattern,
+            replace: request.replace.clone(),
+            host: request.host.clone().filter(|host| !host.is_empty()),
+            previewed: request.previewed.as_ref().map(|links| links.iter().copied().collect()),
+        })
+    }
+
+    /// Fail with [`DbError::Stale`] if `changes` aren't the ones the caller previewed.
+    pub fn check_preview(&self, changes: &[Retarget]) -> DbResult<()> {
+        let Some(previewed) = &self.previewed else {
+            return Ok(());
+        };
+        let current: HashSet<PreviewedLink> = changes
+            .iter()
+            .map(|change| PreviewedLink { id: change.id, version: change.version })
+            .collect();
+        if current == *previewed && current.len() == changes.len() {
+            Ok(())
+        } else {
+            Err(DbError::Stale)
+        }
+    }
+
+    /// Text that every target the rewrite matches contains, so the database can narrow
+    /// the search. A regex gives no such hint.
+    pub fn required_text(&self) -> &str {
+        match &self.pattern {
+            Pattern::Text(find) => find,
+            Pattern::Regex(_) => "",
+        }
+    }
+
+    /// The rewritten target, or `None` if the pattern doesn't match or changes nothing.
+    pub fn apply(&self, target: &str) -> Option<String> {
+        let rewritten = match &self.pattern {
+            Pattern::Text(find) => target.replace(find.as_str(), &self.replace),
+            Pattern::Regex(regex) => regex.replace_all(target, self.replace.as_str()).into_owned(),
+        };
+        (rewritten != target).then_some(rewritten)
+    }
+
+    /// The changes the rewrite makes to `links`, in the order given.
+    pub fn plan(&self, links: &[Link]) -> Vec<Retarget> {
+        links
+            .iter()
+            .filter(|link| self.host.as_ref().is_none_or(|host| *host == link.host))
+            .filter_map(|link| {
+                Some(Retarget {
+                    id: link.id,
+                    host: link.host.clone(),
+                    source: link.source.clone(),
+                    version: link.version,
+                    old_target: link.target.clone(),
+                    new_target: self.apply(&link.target)?,
+                })
+            })
+            .collect()
+    }
+
+    /// How the rewrite is described in each link's revision history.
+    pub fn describe(&self) -> String {
+        match &self.pattern {
+            Pattern::Text(find) => format!("Replaced \"{}\" with \"{}\"", find, self.replace),
+            Pattern::Regex(regex) => format!("Replaced /{}/ with \"{}\"", regex.as_str(), self.replace),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn link(id: i32, host: &str, target: &str) -> Link {
+        Link {
+            id,
+            host: host.to_string(),
+            source: format!("/{}", id),
+            target: target.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            hit_count: 0,
+            version: 1,
+        }
+    }
+
+    fn rewrite(find: &str, replace: &str, regex: bool, host: Option<&str>) -> Result<Rewrite> {
+        Rewrite::new(&RetargetRequest {
+            find: find.to_string(),
+            replace: replace.to_string(),
+            regex,
+            host: host.map(str::to_string),
+            previewed: None,
+        })
+    }
+
+    #[test]
+    fn test_text_rewrite() {
+        let rewrite = rewrite("wiki.old.com", "wiki.new.com", false, None).unwrap();
+        let links = vec![
+            link(1, "go", "https://wiki.old.com/page"),
+            link(2, "go", "https://docs.example.com"),
+            link(3, "t", "https://wiki.old.com/?from=wiki.old.com"),
+        ];
+
+        let plan = rewrite.plan(&links);
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].new_target, "https://wiki.new.com/page");
+        assert_eq!(plan[1].new_target, "https://wiki.new.com/?from=wiki.new.com");
+        assert_eq!(rewrite.required_text(), "wiki.old.com");
+    }
+
+    #[test]
+    fn test_regex_rewrite() {
+        let rewrite = rewrite(r"^http://(\w+)\.example\.com", "https://$1.example.org", true, Some("go")).unwrap();
+        let links = vec![
+            link(1, "go", "http://docs.example.com/a"),
+            link(2, "go", "https://docs.example.com/b"),
+            link(3, "t", "http://docs.example.com/c"),
+        ];
+
+        let plan = rewrite.plan(&links);
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].id, 1);
+        assert_eq!(plan[0].new_target, "https://docs.example.org/a");
+        assert_eq!(rewrite.required_text(), "");
+    }
+
+    #[test]
+    fn test_check_preview() {
+        let links = vec![link(1, "go", "https://wiki.old.com/a"), link(2, "go", "https://wiki.old.com/b")];
+        let preview = |previewed: &[(i32, i32)]| {
+            Rewrite::new(&RetargetRequest {
+                find: "wiki.old.com".to_string(),
+                replace: "wiki.new.com".to_string(),
+                regex: false,
+                host: None,
+                previewed: Some(previewed.iter().map(|&(id, version)| PreviewedLink { id, version }).collect()),
+            })
+            .unwrap()
+        };
+
+        let rewrite = preview(&[(1, 1), (2, 1)]);
+        assert!(rewrite.check_preview(&rewrite.plan(&links)).is_ok());
+        // A link changed, or started matching, since the preview
+        let rewrite = preview(&[(1, 1), (2, 0)]);
+        assert!(matches!(rewrite.check_preview(&rewrite.plan(&links)), Err(DbError::Stale)));
+        let rewrite = preview(&[(1, 1)]);
+        assert!(matches!(rewrite.check_preview(&rewrite.plan(&links)), Err(DbError::Stale)));
+    }
+
+    #[test]
+    fn test_invalid_rewrite() {
+        assert!(rewrite("", "x", false, None).is_err());
+        assert!(rewrite("(unclosed", "x", true, None).is_err());
+        // Replacing text with itself changes nothing
+        assert!(rewrite("a", "a", false, None).unwrap().apply("abc").is_none());
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::cache::{LinkCache, LinkMap};
+use crate::models::Link;
+
+/// How often the cache is checked for changes worth saving.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The redirect cache as saved to disk.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub saved_at: DateTime<Utc>,
+    pub links: Vec<Link>,
+}
+
+/// Write `links` to `path`. The file is replaced in one rename, so a crash mid-write
+/// leaves the previous snapshot intact.
+pub async fn save(path: &Path, links: &LinkMap) -> Result<()> {
+    let snapshot = Snapshot {
+        saved_at: Utc::now(),
+        links: links.values().cloned().collect(),
+    };
+    let contents = serde_json::to_vec(&snapshot)?;
+
+    let partial = path.with_extension("partial");
+    tokio::fs::write(&partial, contents)
+        .await
+        .with_context(|| format!("Failed to write cache snapshot {}", partial.display()))?;
+    tokio::fs::rename(&partial, path)
+        .await
+        .with_context(|| format!("Failed to replace cache snapshot {}", path.display()))?;
+    Ok(())
+}
+
+/// Read a snapshot written by [`save`].
+pub async fn load(path: &Path) -> Result<Snapshot> {
+    let contents = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read cache snapshot {}", path.display()))?;
+    serde_json::from_slice(&contents).with_context(|| format!("Invalid cache snapshot {}", path.display()))
+}
+
+impl Snapshot {
+    pub fn into_link_map(self) -> LinkMap {
+        self.links
+            .into_iter()
+            .map(|link| ((link.host.clone(), link.source.clone()), link))
+            .collect()
+    }
+}
+
+/// Save the cache to `path` each time it changes from what it holds now.
+pub fn spawn_writer(cache: LinkCache, path: PathBuf) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut saved = cache.snapshot();
+        let mut ticker = tokio::time::interval(SAVE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let current = cache.snapshot();
+            // Every change swaps in a new map, so an unchanged pointer means nothing to save
+            if Arc::ptr_eq(&saved, &current) {
+                continue;
+            }
+            match save(&path, &current).await {
+                Ok(()) => saved = current,
+                Err(e) => tracing::warn!("{:#}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+
+        let link = Link {
+            id: 1,
+            host: "go".to_string(),
+            source: "/docs".to_string(),
+            target: "https://docs.example.com".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            hit_count: 3,
+            version: 1,
+        };
+        let links: LinkMap = [(("go".to_string(), "/docs".to_string()), link)].into_iter().collect();
+        save(&path, &links).await.unwrap();
+
+        let loaded = load(&path).await.unwrap().into_link_map();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[&("go".to_string(), "/docs".to_string())].target, "https://docs.example.com");
+        assert!(!path.with_extension("partial").exists());
+
+        tokio::fs::write(&path, "not json").await.unwrap();
+        assert!(load(&path).await.is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::import::normalize_source;
+use crate::models::{CreateLinkRequest, Link};
+
+/// A declarative links file: every link under `scope` that isn't listed is deleted.
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub scope: SyncScope,
+    #[serde(default)]
+    pub links: Vec<ManifestLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncScope {
+    pub host: String,
+    #[serde(default)]
+    pub prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManifestLink {
+    pub host: Option<String>,
+    pub source: String,
+    pub target: String,
+}
+
+impl SyncScope {
+    /// The prefix without a trailing slash: the scope covers it and every path below it.
+    pub fn path_prefix(&self) -> &str {
+        self.prefix.trim_end_matches('/')
+    }
+
+    /// Whether the link is in scope. The prefix only matches whole path segments, so
+    /// `/team` covers `/team` and `/team/docs` but not `/teamwork`.
+    pub fn contains(&self, host: &str, source: &str) -> bool {
+        let prefix = self.path_prefix();
+        host == self.host
+            && source
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+impl Manifest {
+    pub fn parse(input: &str) -> Result<Self> {
+        // YAML is a superset of JSON, so this accepts either
+        let mut manifest: Manifest = serde_yaml::from_str(input).map_err(|e| anyhow!("Invalid manifest: {}", e))?;
+        if manifest.scope.host.trim().is_empty() {
+            return Err(anyhow!("Manifest scope must name a host"));
+        }
+
+        if !manifest.scope.prefix.is_empty() {
+            manifest.scope.prefix = normalize_source(&manifest.scope.prefix);
+        }
+        for link in &mut manifest.links {
+            link.source = normalize_source(&link.source);
+        }
+        Ok(manifest)
+    }
+
+    /// Rewrite the scope host and every link host, e.g. to their canonical form.
+    pub fn map_hosts(&mut self, f: impl Fn(&str) -> String) {
+        self.scope.host = f(&self.scope.host);
+        for link in &mut self.links {
+            link.host = link.host.as_deref().map(&f);
+        }
+    }
+
+    /// The desired links, rejecting any that fall outside the declared scope.
+    pub fn desired_links(&self) -> Result<Vec<CreateLinkRequest>> {
+        let mut seen = HashSet::new();
+        let mut links = Vec::new();
+
+        for link in &self.links {
+            let host = link.host.clone().unwrap_or_else(|| self.scope.host.clone());
+            if !self.scope.contains(&host, &link.source) {
+                return Err(anyhow!(
+                    "Link '{}{}' is outside the manifest scope '{}{}'",
+                    host, link.source, self.scope.host, self.scope.prefix
+                ));
+            }
+            if !seen.insert(link.source.clone()) {
+                return Err(anyhow!("Link '{}{}' is listed more than once", host, link.source));
+            }
+
+            links.push(CreateLinkRequest {
+                host,
+                source: link.source.clone(),
+                target: link.target.clone(),
+            });
+        }
+
+        Ok(links)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncUpdate {
+    pub id: i32,
+    pub host: String,
+    pub source: String,
+    pub old_target: String,
+    pub new_target: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncDelete {
+    pub id: i32,
+    pub host: String,
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncCreate {
+    pub host: String,
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncPlan {
+    pub create: Vec<SyncCreate>,
+    pub update: Vec<SyncUpdate>,
+    pub delete: Vec<SyncDelete>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncReport {
+    pub applied: bool,
+    pub plan: SyncPlan,
+}
+
+impl SyncPlan {
+    /// Diff the links currently in scope against the desired ones.
+    pub fn compute(existing: &[Link], desired: &[CreateLinkRequest]) -> Self {
+        let mut plan = SyncPlan::default();
+        let current: HashMap<&str, &Link> = existing.iter().map(|l| (l.source.as_str(), l)).collect();
+        let wanted: HashSet<&str> = desired.iter().map(|l| l.source.as_str()).collect();
+
+        for link in desired {
+            match current.get(link.source.as_str()) {
+                Some(existing) if existing.target == link.target => plan.unchanged += 1,
+                Some(existing) => plan.update.push(SyncUpdate {
+                    id: existing.id,
+                    host: existing.host.clone(),
+                    source: existing.source.clone(),
+                    old_target: existing.target.clone(),
+                    new_target: link.target.clone(),
+                }),
+                None => plan.create.push(SyncCreate {
+                    host: link.host.clone(),
+                    source: link.source.clone(),
+                    target: link.target.clone(),
+                }),
+            }
+        }
+
+        for link in existing {
+            if !wanted.contains(link.source.as_str()) {
+                plan.delete.push(SyncDelete {
+                    id: link.id,
+                    host: link.host.clone(),
+                    source: link.source.clone(),
+                    target: link.target.clone(),
+                });
+            }
+        }
+
+        plan
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty() && self.delete.is_empty()
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for link in &self.create {
+            writeln!(f, "+ {}{} -> {}", link.host, link.source, link.target)?;
+        }
+        for link in &self.update {
+            writeln!(f, "~ {}{}: {} -> {}", link.host, link.source, link.old_target, link.new_target)?;
+        }
+        for link in &self.delete {
+            writeln!(f, "- {}{} -> {}", link.host, link.source, link.target)?;
+        }
+        write!(
+            f,
+            "{} to create, {} to update, {} to delete, {} unchanged",
+            self.create.len(),
+            self.update.len(),
+            self.delete.len(),
+            self.unchanged
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn create_test_link(id: i32, source: &str, target: &str) -> Link {
+        Link {
+            id,
+            host: "go".to_string(),
+            source: source.to_string(),
+            target: target.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            hit_count: 0,
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn test_manifest_rejects_links_outside_scope() {
+        let manifest = Manifest::parse(
+            "scope:\n  host: go\n  prefix: /team\nlinks:\n  - source: /other\n    target: https://example.com\n",
+        )
+        .unwrap();
+        assert!(manifest.desired_links().is_err());
+
+        let manifest = Manifest::parse(
+            "scope:\n  host: go\nlinks:\n  - host: docs\n    source: /x\n    target: https://example.com\n",
+        )
+        .unwrap();
+        assert!(manifest.desired_links().is_err());
+
+        // A prefix ends at a path segment
+        let manifest = Manifest::parse(
+            "scope:\n  host: go\n  prefix: team/\nlinks:\n  - source: teamwork\n    target: https://example.com\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.links[0].source, "/teamwork");
+        assert!(manifest.desired_links().is_err());
+        assert!(manifest.scope.contains("go", "/team"));
+        assert!(manifest.scope.contains("go", "/team/docs"));
+        assert!(!manifest.scope.contains("go", "/teams-old"));
+    }
+
+    #[test]
+    fn test_plan() {
+        let existing = vec![
+            create_test_link(1, "/team/docs", "https://docs.example.com"),
+            create_test_link(2, "/team/wiki", "https://old-wiki.example.com"),
+            create_test_link(3, "/team/gone", "https://gone.example.com"),
+        ];
+        let manifest = Manifest::parse(
+            r#"
+scope:
+  host: go
+  prefix: /team
+links:
+  - source: /team/docs
+    target: https://docs.example.com
+  - source: /team/wiki
+    target: https://wiki.example.com
+  - source: /team/new
+    target: https://new.example.com
+"#,
+        )
+        .unwrap();
+
+        let plan = SyncPlan::compute(&existing, &manifest.desired_links().unwrap());
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(plan.create.len(), 1);
+        assert_eq!(plan.create[0].source, "/team/new");
+        assert_eq!(plan.update.len(), 1);
+        assert_eq!(plan.update[0].id, 2);
+        assert_eq!(plan.delete.len(), 1);
+        assert_eq!(plan.delete[0].id, 3);
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use axum::http::HeaderMap;
+use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Exports spans over OTLP/HTTP to the collector at `endpoint`, e.g. `http://localhost:4318`.
+/// Also installs the W3C trace-context propagator, so incoming `traceparent` headers are honoured.
+pub fn tracer_provider(endpoint: &str) -> Result<TracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .with_context(|| format!("Failed to create OTLP exporter for {}", endpoint))?;
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", env!("CARGO_PKG_NAME"))]))
+        .build())
+}
+
+/// A `tracing` layer that turns spans into OpenTelemetry spans for `provider`.
+pub fn layer<S>(provider: &TracerProvider) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+}
+
+/// Continue the trace the caller started, if its headers carry one. Without an exporter
+/// the propagator is a no-op and this does nothing.
+pub fn set_parent(span: &tracing::Span, headers: &HeaderMap) {
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    span.set_parent(parent);
+}
+
+/// Send spans that are still buffered. Blocks, so call it off the async runtime.
+pub fn shutdown(provider: TracerProvider) {
+    if let Err(e) = provider.shutdown() {
+        eprintln!("Failed to flush traces: {}", e);
+    }
+}
@@ -1,18 +1,33 @@
 use askama::Template;
-use crate::models::LinkResponse;
+use crate::models::{ImportReport, LinkResponse};
+use crate::redirector::Explanation;
+use crate::retarget::RetargetReport;
 
 #[derive(Template)]
 #[template(path = "home.html")]
-pub struct HomeTemplate {
+pub struct HomeTemplate<'a> {
     pub links: Vec<LinkResponse>,
+    pub total: i64,
+    pub host: &'a str,
+    pub sort: &'a str,
+    pub order: &'a str,
+    pub is_first_page: bool,
+    pub next_cursor: Option<String>,
+    /// Prefix for links to admin pages; empty when served from the admin host
+    pub admin_url: &'a str,
+    /// Show the read-only banner
+    pub read_only: bool,
 }
 
 #[derive(Template)]
 #[template(path = "add.html")]
 pub struct AddTemplate<'a> {
     pub source: &'a str,
+    pub host: &'a str,
     pub error: Option<&'a str>,
     pub default_redirect_host: &'a str,
+    /// Show the read-only banner
+    pub read_only: bool,
 }
 
 #[derive(Template)]
@@ -20,6 +35,12 @@ pub struct AddTemplate<'a> {
 pub struct EditTemplate<'a> {
     pub link: &'a LinkResponse,
     pub error: Option<&'a str>,
+    /// Prefix for links to admin pages; empty when served from the admin host
+    pub admin_url: &'a str,
+    /// Show the read-only banner
+    pub read_only: bool,
+    /// The result of the path tester, when a path was submitted
+    pub test: Option<Explanation>,
 }
 
 #[derive(Template)]
@@ -28,4 +49,32 @@ pub struct SearchTemplate<'a> {
     pub query: &'a str,
     pub links: Vec<LinkResponse>,
     pub page: i32,
+    /// Show the read-only banner
+    pub read_only: bool,
+}
+
+#[derive(Template)]
+#[template(path = "import.html")]
+pub struct ImportTemplate<'a> {
+    pub data: &'a str,
+    pub format: &'a str,
+    pub policy: &'a str,
+    pub host: &'a str,
+    pub error: Option<&'a str>,
+    pub report: Option<ImportReport>,
+    /// Show the read-only banner
+    pub read_only: bool,
+}
+
+#[derive(Template)]
+#[template(path = "retarget.html")]
+pub struct RetargetTemplate<'a> {
+    pub find: &'a str,
+    pub replace: &'a str,
+    pub regex: bool,
+    pub host: &'a str,
+    pub error: Option<&'a str>,
+    pub report: Option<RetargetReport>,
+    /// Show the read-only banner
+    pub read_only: bool,
 }
@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::{header::HOST, HeaderMap, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+
+use crate::config::{normalize_host, TlsConfig};
+
+/// Load the certificate and key, failing startup if either can't be used.
+pub async fn load(tls: &TlsConfig) -> Result<RustlsConfig> {
+    // Several crates may pull in rustls; pick the provider explicitly so they agree
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    RustlsConfig::from_pem_file(&tls.cert, &tls.key).await.with_context(|| {
+        format!("Failed to load TLS certificate {} and key {}", tls.cert.display(), tls.key.display())
+    })
+}
+
+/// Re-read the certificate and key whenever the process gets SIGHUP. New handshakes use the
+/// new certificate; open connections keep going. A failed reload keeps the old certificate.
+#[cfg(unix)]
+pub fn spawn_reload_on_sighup(rustls: RustlsConfig, tls: TlsConfig) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGHUP, certificates won't be reloaded: {}", e);
+                return;
+            }
+        };
+
+        while hangups.recv().await.is_some() {
+            match rustls.reload_from_pem_file(&tls.cert, &tls.key).await {
+                Ok(()) => tracing::info!("Reloaded TLS certificate from {}", tls.cert.display()),
+                Err(e) => tracing::error!("Failed to reload TLS certificate, keeping the old one: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn spawn_reload_on_sighup(_rustls: RustlsConfig, _tls: TlsConfig) {}
+
+/// A plain HTTP app that sends every request to the same URL over HTTPS on `https_port`.
+pub fn redirect_app(https_port: u16) -> Router {
+    Router::new().fallback(redirect_to_https).with_state(https_port)
+}
+
+async fn redirect_to_https(State(https_port): State<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    let host = headers
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| uri.host());
+
+    match host {
+        Some(host) => {
+            let path = uri.path_and_query().map_or("/", |path| path.as_str());
+            Redirect::permanent(&https_url(host, https_port, path)).into_response()
+        }
+        None => (axum::http::StatusCode::BAD_REQUEST, "No host found in request").into_response(),
+    }
+}
+
+/// `host` with its port replaced by `port`, which is left out when it's the HTTPS default.
+fn https_url(host: &str, port: u16, path: &str) -> String {
+    let host = normalize_host(host);
+    if port == 443 {
+        format!("https://{}{}", host, path)
+    } else {
+        format!("https://{}:{}{}", host, port, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_https_url() {
+        assert_eq!(https_url("go", 443, "/docs?q=1"), "https://go/docs?q=1");
+        assert_eq!(https_url("go:80", 443, "/"), "https://go/");
+        assert_eq!(https_url("Go:8080", 8443, "/docs"), "https://go:8443/docs");
+        assert_eq!(https_url("[::1]:80", 443, "/"), "https://[::1]/");
+    }
+}
@@ -2,34 +2,41 @@ use axum::{
     body::Body,
     http::{Request, StatusCode, header::HOST},
 };
-use serde_json::json;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
 use tower::util::ServiceExt;
 
 use lynx::*;
 
-async fn create_test_app() -> axum::Router {
+fn test_config() -> config::Config {
     // Load test environment
     dotenvy::from_filename(".env.test").ok();
     
     // Create test configuration from environment
-    let config = config::Config::from_env().expect("Failed to load test config");
+    config::Config::from_env().expect("Failed to load test config")
+}
+
+async fn create_test_app() -> axum::Router {
+    create_test_app_with_config(test_config()).await
+}
 
+async fn create_test_app_with_config(config: config::Config) -> axum::Router {
+    create_app(create_test_state(config).await)
+}
+
+async fn create_test_state(config: config::Config) -> AppState {
     // Create in-memory cache
-    let cache = Arc::new(RwLock::new(HashMap::new()));
+    let cache = LinkCache::new(HashMap::new());
 
     // Connect to test database
     let db = database::Database::new(&config.database_url).await.expect("Failed to connect to test database");
 
-    let state = AppState {
+    AppState {
         db,
         cache,
+        cache_status: Default::default(),
         config,
-    };
-
-    create_app(state)
+        health: Default::default(),
+    }
 }
 
 #[tokio::test]
@@ -81,9 +88,1014 @@ async fn test_redirector_no_match() {
     assert!(location_str.contains("source=%2Fnonexistent")); // URL encoded /nonexistent
 }
 
+#[tokio::test]
+async fn test_multiple_redirect_hosts() {
+    use config::{HostConfig, MissBehaviour};
+
+    let suffix = uuid::Uuid::new_v4();
+    let go = format!("go-{}", suffix);
+    let mut config = test_config();
+    config.hosts.push(HostConfig {
+        aliases: vec![format!("{}.corp.example.com", go)],
+        ..HostConfig::new(&go, false)
+    });
+    config.hosts.push(HostConfig {
+        on_miss: MissBehaviour::NotFound,
+        ..HostConfig::new(&format!("t-{}", suffix), false)
+    });
+    config.hosts.push(HostConfig {
+        on_miss: MissBehaviour::Redirect("https://docs.example.com/search?q={path}".to_string()),
+        ..HostConfig::new(&format!("docs-{}", suffix), false)
+    });
+    let app = create_test_app_with_config(config).await;
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/links")
+        .header(HOST, "lynx")
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::json!({"host": go, "source": "/wiki", "target": "https://wiki.example.com"}).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let link: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let get = |host: String, path: &str| {
+        Request::builder().uri(path).header(HOST, host).body(Body::empty()).unwrap()
+    };
+
+    // Aliases resolve to the canonical host's links
+    let response = app.clone().oneshot(get(format!("{}.corp.example.com", go), "/wiki")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers()["location"], "https://wiki.example.com");
+
+    // Admin UI paths on a redirect host are treated as links
+    let response = app.clone().oneshot(get(go.clone(), "/search")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    let location = response.headers()["location"].to_str().unwrap();
+    assert!(location.contains("/add?source=%2Fsearch"));
+    assert!(location.contains(&format!("host={}", go)));
+
+    let response = app.clone().oneshot(get(format!("t-{}", suffix), "/missing")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app.clone().oneshot(get(format!("docs-{}", suffix), "/missing")).await.unwrap();
+    assert_eq!(response.headers()["location"], "https://docs.example.com/search?q=missing");
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/links/{}", link["id"]))
+        .header(HOST, "lynx")
+        .header("if-match", "*")
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(request).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_normalization() {
+    use config::HostConfig;
+
+    let go = format!("go-{}", uuid::Uuid::new_v4());
+    let mut config = test_config();
+    config.hosts.push(HostConfig {
+        aliases: vec![format!("{}.corp.example.com", go)],
+        ..HostConfig::new(&go, false)
+    });
+    let app = create_test_app_with_config(config).await;
+
+    // Links created through an alias are stored under the canonical host
+    let alias = format!("{}.Corp.Example.com", go.to_uppercase());
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/links")
+        .header(HOST, "LYNX:3100")
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::json!({"host": alias, "source": "/wiki", "target": "https://wiki.example.com"}).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let link: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(link["host"], go.as_str());
+
+    for host in [format!("{}:3100", go.to_uppercase()), format!("{}.corp.example.com.", go)] {
+        let request = Request::builder().uri("/wiki").header(HOST, host).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()["location"], "https://wiki.example.com");
+    }
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/links/{}", link["id"]))
+        .header(HOST, "lynx")
+        .header("if-match", "*")
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(request).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_forwarded_headers_from_trusted_proxies() {
+    use axum::extract::ConnectInfo;
+    use config::{HostConfig, MissBehaviour};
+
+    let go = format!("go-{}", uuid::Uuid::new_v4());
+    let mut config = test_config();
+    config.public_url = None;
+    config.trusted_proxies = vec!["10.0.0.0/8".parse().unwrap()];
+    config.hosts.push(HostConfig::new(&go, false));
+    // A host that would reveal it was picked instead of the forwarded one
+    config.hosts.push(HostConfig {
+        on_miss: MissBehaviour::NotFound,
+        ..HostConfig::new("internal", false)
+    });
+    let admin_host = config.admin_host.clone();
+    let app = create_test_app_with_config(config.clone()).await;
+
+    let forwarded = |peer: &str| {
+        let mut request = Request::builder()
+            .uri("/missing")
+            .header(HOST, "internal:3100")
+            .header("x-forwarded-host", go.as_str())
+            .header("x-forwarded-proto", "https")
+            .body(Body::empty())
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo(format!("{}:4000", peer).parse::<std::net::SocketAddr>().unwrap()));
+        request
+    };
+
+    let response = app.clone().oneshot(forwarded("10.1.2.3")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    let location = response.headers()["location"].to_str().unwrap();
+    assert!(location.starts_with(&format!("https://{}/add?", admin_host)), "{}", location);
+
+    // Anyone else's forwarded headers are ignored
+    let response = app.clone().oneshot(forwarded("192.168.1.1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Pages served on a redirect host link back to the admin UI
+    let request = Request::builder().uri("/admin").header(HOST, go.as_str()).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains(&format!("href=\"{}/add\"", config.admin_url())));
+
+    config.public_url = Some("https://lynx.example.com".to_string());
+    let app = create_test_app_with_config(config).await;
+    let request = Request::builder().uri("/missing").header(HOST, go.as_str()).body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    let location = response.headers()["location"].to_str().unwrap();
+    assert!(location.starts_with("https://lynx.example.com/add?"), "{}", location);
+}
+
+#[tokio::test]
+async fn test_health_and_readiness() {
+    let probe = |path: &str| Request::builder().uri(path).header(HOST, "go").body(Body::empty()).unwrap();
+    let read_json = |response: axum::response::Response| async {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    let app = create_test_app().await;
+
+    // Probes answer on redirect hosts instead of being treated as links
+    let response = app.clone().oneshot(probe("/healthz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Not ready until the cache has been loaded
+    let response = app.oneshot(probe("/readyz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let readiness = read_json(response).await;
+    assert_eq!(readiness["database"], "ok");
+    assert_eq!(readiness["cache_loaded"], false);
+
+    // A fully migrated store with a loaded cache is ready
+    let mut config = test_config();
+    config.database_url = "sqlite::memory:".to_string();
+    let state = create_test_state(config).await;
+    let app = create_app(state.clone());
+
+    state.cache_status.write().await.last_reload = Some(chrono::Utc::now());
+    let response = app.clone().oneshot(probe("/readyz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let readiness = read_json(response).await;
+    assert_eq!(readiness["ready"], true);
+    assert_eq!(readiness["pending_migrations"], 0);
+
+    // Serving read-only from the cache still counts as ready, so redirects keep flowing
+    state.health.set_read_only(true);
+    let response = app.clone().oneshot(probe("/readyz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let readiness = read_json(response).await;
+    assert_eq!(readiness["read_only"], true);
+    assert_eq!(readiness["database"], "unavailable");
+
+    state.health.start_draining();
+    let response = app.oneshot(probe("/readyz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_metrics() {
+    let mut config = test_config();
+    config.metrics_port = None;
+    let state = create_test_state(config).await;
+    let app = create_app(state.clone());
+
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+    let link = state
+        .db
+        .create_link(&models::CreateLinkRequest {
+            host: host.clone(),
+            source: "/docs".to_string(),
+            target: "https://docs.example.com".to_string(),
+        })
+        .await
+        .unwrap();
+    state.cache.insert(link.clone()).await;
+
+    for path in ["/docs", "/docs/api", "/missing"] {
+        let request = Request::builder().uri(path).header(HOST, host.as_str()).body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap();
+    }
+
+    let request = Request::builder().uri("/metrics").header(HOST, "lynx").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+
+    for outcome in ["exact", "prefix", "miss"] {
+        assert!(text.contains(&format!("lynx_redirects_total{{outcome=\"{}\"}}", outcome)), "{}", outcome);
+    }
+    assert!(text.contains("lynx_http_requests_total{group=\"redirect\",method=\"GET\",status=\"307\"}"));
+    assert!(text.contains(&format!("lynx_cache_entries{{host=\"{}\"}} 1", host)));
+    assert!(text.contains("lynx_db_query_duration_seconds_count{operation=\"create_link\"}"));
+
+    // On configured redirect hosts the path is an ordinary link
+    let redirect_host = state.config.default_redirect_host.clone();
+    let request = Request::builder().uri("/metrics").header(HOST, redirect_host).body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+
+    state.db.delete_link(link.id, None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_api_list_links_pagination() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+
+    for source in ["/a", "/b", "/c"] {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/links")
+            .header(HOST, "lynx")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({"host": host, "source": source, "target": "https://example.com"}).to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let request = Request::builder()
+        .uri(format!("/api/links?host={}&sort=source&limit=2", host))
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-total-count"], "3");
+    let cursor = response.headers()["x-next-cursor"].to_str().unwrap().to_string();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let first_page: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+    let sources: Vec<&str> = first_page.iter().map(|l| l["source"].as_str().unwrap()).collect();
+    assert_eq!(sources, vec!["/a", "/b"]);
+
+    let request = Request::builder()
+        .uri(format!("/api/links?host={}&sort=source&limit=2&cursor={}", host, cursor))
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("x-next-cursor").is_none());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let links: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0]["source"], "/c");
+
+    for link in first_page.into_iter().chain(links) {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/links/{}", link["id"]))
+            .header(HOST, "lynx")
+            .header("if-match", "*")
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_api_import_links() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+    let csv = "source,target\n/a,https://a.example.com\n/b,https://b.example.com\n";
+
+    let import = |query: String, body: &'static str| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/api/links/import?format=csv&host={}&{}", host, query))
+            .header(HOST, "lynx")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(import("dry_run=true".to_string(), csv)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["created"], 2);
+    assert_eq!(report["applied"], false);
+
+    let response = app.clone().oneshot(import("policy=fail".to_string(), csv)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Re-importing the same links conflicts and must leave the database untouched
+    let response = app.clone().oneshot(import("policy=fail".to_string(), "source,target\n/c,https://c.example.com\n/a,https://x.example.com\n")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = app.clone().oneshot(import("policy=overwrite".to_string(), "source,target\n/a,https://x.example.com\n")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["updated"], 1);
+
+    let request = Request::builder()
+        .uri(format!("/api/links?host={}", host))
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["x-total-count"], "2");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let links: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+
+    for link in links {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/links/{}", link["id"]))
+            .header(HOST, "lynx")
+            .header("if-match", "*")
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_api_export_links() {
+    let app = create_test_app().await;
+
+    let request = Request::builder()
+        .uri("/api/links/export?format=ndjson")
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    for line in String::from_utf8(body.to_vec()).unwrap().lines() {
+        let link: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert!(link["target"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn test_api_sync_links() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+
+    let sync = |query: &str, manifest: String| {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/api/links/sync?{}", query))
+            .header(HOST, "lynx")
+            .body(Body::from(manifest))
+            .unwrap()
+    };
+    let manifest = format!(
+        "scope:\n  host: {host}\n  prefix: /team\nlinks:\n  - source: /team/docs\n    target: https://docs.example.com\n"
+    );
+
+    let response = app.clone().oneshot(sync("dry_run=true", manifest.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["applied"], false);
+    assert_eq!(report["plan"]["create"].as_array().unwrap().len(), 1);
+
+    let response = app.clone().oneshot(sync("", manifest)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/team/docs/page")
+        .header(HOST, host.as_str())
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["location"], "https://docs.example.com");
+
+    // Links outside the scope are refused
+    let outside = format!(
+        "scope:\n  host: {host}\n  prefix: /team\nlinks:\n  - source: /other\n    target: https://example.com\n"
+    );
+    let response = app.clone().oneshot(sync("", outside)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // An empty manifest deletes everything in scope
+    let response = app.clone().oneshot(sync("", format!("scope:\n  host: {host}\n  prefix: /team\n"))).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["plan"]["delete"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_cache_listener_applies_changes_from_other_instances() {
+    dotenvy::from_filename(".env.test").ok();
+    let config = config::Config::from_env().expect("Failed to load test config");
+    let db = database::Database::new(&config.database_url).await.expect("Failed to connect to test database");
+    if !database::PostgresStore::handles(&config.database_url) {
+        // Only Postgres can notify other instances
+        return;
+    }
+    let cache = LinkCache::new(HashMap::new());
+    let handle = listener::spawn_cache_listener(config.database_url.clone(), db.clone(), cache.clone(), Default::default());
+
+    // Wait for the initial resync so we know the listener is subscribed
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+    let marker = db
+        .create_link(&models::CreateLinkRequest {
+            host: host.clone(),
+            source: "/marker".to_string(),
+            target: "https://example.com".to_string(),
+        })
+        .await
+        .unwrap();
+    let wait_for = |key: (String, String), present: bool| {
+        let cache = cache.clone();
+        async move {
+            for _ in 0..100 {
+                if cache.snapshot().contains_key(&key) == present {
+                    return true;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            false
+        }
+    };
+    assert!(wait_for((host.clone(), "/marker".to_string()), true).await);
+
+    // A write made directly against the database, as another replica would
+    let link = db
+        .create_link(&models::CreateLinkRequest {
+            host: host.clone(),
+            source: "/a".to_string(),
+            target: "https://a.example.com".to_string(),
+        })
+        .await
+        .unwrap();
+    assert!(wait_for((host.clone(), "/a".to_string()), true).await);
+
+    let update = models::UpdateLinkRequest {
+        host: None,
+        source: Some("/b".to_string()),
+        target: None,
+    };
+    db.update_link(link.id, &update, None).await.unwrap();
+    assert!(wait_for((host.clone(), "/b".to_string()), true).await);
+    assert!(wait_for((host.clone(), "/a".to_string()), false).await);
+
+    db.delete_link(link.id, None).await.unwrap();
+    db.delete_link(marker.id, None).await.unwrap();
+    assert!(wait_for((host.clone(), "/b".to_string()), false).await);
+    assert!(wait_for((host.clone(), "/marker".to_string()), false).await);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_api_cache_reload() {
+    let app = create_test_app().await;
+
+    // The test app starts with an empty cache, so anything in the database counts as drift
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/links")
+        .header(HOST, "lynx")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            serde_json::json!({"host": format!("test-{}", uuid::Uuid::new_v4()), "source": "/a", "target": "https://example.com"}).to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let link: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/admin/cache/reload")
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/api/admin/cache")
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(status["entries"].as_u64().unwrap() >= 1);
+    assert_eq!(status["reloads"], 1);
+    assert!(status["last_reload"].is_string());
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/links/{}", link["id"]))
+        .header(HOST, "lynx")
+        .header("if-match", "*")
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(request).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_api_concurrent_create_conflicts() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+
+    // Racing creates of the same link: the unique index lets exactly one through
+    let requests = (0..8).map(|_| {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/links")
+            .header(HOST, "lynx")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({"host": host, "source": "/race", "target": "https://example.com"}).to_string(),
+            ))
+            .unwrap();
+        app.clone().oneshot(request)
+    });
+    let responses = futures::future::join_all(requests).await;
+
+    let mut created = None;
+    let mut conflicts = 0;
+    for response in responses {
+        let response = response.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        match status {
+            StatusCode::OK => created = Some(body["id"].clone()),
+            StatusCode::CONFLICT => {
+                assert!(body["error"].as_str().unwrap().contains("already exists"));
+                conflicts += 1;
+            }
+            other => panic!("unexpected status {}", other),
+        }
+    }
+    assert_eq!(conflicts, 7);
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/links/{}", created.unwrap()))
+        .header(HOST, "lynx")
+        .header("if-match", "*")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Deleting a link that doesn't exist reports it as missing
+    let request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/links/{}", i32::MAX))
+        .header(HOST, "lynx")
+        .header("if-match", "*")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_link_versions() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/links")
+        .header(HOST, "lynx")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            serde_json::json!({"host": host, "source": "/v", "target": "https://example.com/1"}).to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let link: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(link["version"], 1);
+    let uri = format!("/api/links/{}", link["id"]);
+
+    let request = Request::builder().uri(&uri).header(HOST, "lynx").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"1\"");
+
+    let update = |if_match: Option<&str>| {
+        let mut request = Request::builder()
+            .method("PUT")
+            .uri(&uri)
+            .header(HOST, "lynx")
+            .header("content-type", "application/json");
+        if let Some(if_match) = if_match {
+            request = request.header("if-match", if_match);
+        }
+        request
+            .body(Body::from(serde_json::json!({"target": "https://example.com/2"}).to_string()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(update(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = app.clone().oneshot(update(Some("\"1\""))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["etag"], "\"2\"");
+
+    // A second edit from the same stale copy is refused
+    let response = app.clone().oneshot(update(Some("\"1\""))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(error["error"].as_str().unwrap().contains("changed"));
+
+    // The same goes for the edit form, which shows the current version instead
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/edit/{}", link["id"]))
+        .header(HOST, "lynx")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(format!("host={}&source=%2Fv&target=https%3A%2F%2Fexample.com%2F3&version=1", host)))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let page = String::from_utf8_lossy(&body);
+    assert!(page.contains("Someone else changed this link"));
+    assert!(page.contains("https://example.com/2"));
+
+    let delete = |if_match: &str| {
+        Request::builder()
+            .method("DELETE")
+            .uri(&uri)
+            .header(HOST, "lynx")
+            .header("if-match", if_match)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = app.clone().oneshot(delete("\"1\"")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = app.oneshot(delete("\"2\"")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_batch() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+
+    let batch = |operations: serde_json::Value| {
+        Request::builder()
+            .method("POST")
+            .uri("/api/links/batch")
+            .header(HOST, "lynx")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::json!({"operations": operations}).to_string()))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(batch(serde_json::json!([
+            {"op": "create", "host": host, "source": "/a", "target": "https://example.com/a"},
+            {"op": "create", "host": host, "source": "/b", "target": "https://example.com/b"},
+        ])))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["applied"], true);
+    let a = report["results"][0]["link"].clone();
+    let b = report["results"][1]["link"].clone();
+
+    // A stale version and a duplicate source fail; the retarget before them is rolled back
+    let response = app
+        .clone()
+        .oneshot(batch(serde_json::json!([
+            {"op": "update", "id": a["id"], "target": "https://example.com/a2"},
+            {"op": "update", "id": b["id"], "version": 99, "target": "https://example.com/b2"},
+            {"op": "create", "host": host, "source": "/a", "target": "https://example.com/dup"},
+        ])))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["applied"], false);
+    assert_eq!(report["results"][0]["ok"], true);
+    assert!(report["results"][1]["error"].as_str().unwrap().contains("changed"));
+    assert!(report["results"][2]["error"].as_str().unwrap().contains("already exists"));
+
+    let request = Request::builder().uri("/a").header(HOST, host.as_str()).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["location"], "https://example.com/a");
+
+    // Once committed, the cache follows
+    let response = app
+        .clone()
+        .oneshot(batch(serde_json::json!([
+            {"op": "update", "id": a["id"], "version": a["version"], "target": "https://example.com/a2"},
+            {"op": "delete", "id": b["id"], "version": b["version"]},
+        ])))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder().uri("/a").header(HOST, host.as_str()).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["location"], "https://example.com/a2");
+    let request = Request::builder().uri("/b").header(HOST, host.as_str()).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_ne!(response.headers().get("location").map(|v| v.to_str().unwrap()), Some("https://example.com/b"));
+
+    let response = app.oneshot(batch(serde_json::json!([{"op": "delete", "id": a["id"]}]))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_retarget() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+
+    let post = |uri: &str, body: serde_json::Value| {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(HOST, "lynx")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(post("/api/links/batch", serde_json::json!({"operations": [
+            {"op": "create", "host": host, "source": "/a", "target": "https://wiki.old.com/a"},
+            {"op": "create", "host": host, "source": "/b", "target": "https://docs.example.com/b"},
+        ]})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let a = report["results"][0]["link"].clone();
+    let b = report["results"][1]["link"].clone();
+
+    let retarget = serde_json::json!({"find": "wiki.old.com", "replace": "wiki.new.com", "host": host});
+    let response = app.clone().oneshot(post("/api/links/retarget?dry_run=true", retarget.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["applied"], false);
+    assert_eq!(report["changes"].as_array().unwrap().len(), 1);
+    assert_eq!(report["changes"][0]["new_target"], "https://wiki.new.com/a");
+
+    let request = Request::builder().uri("/a").header(HOST, host.as_str()).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["location"], "https://wiki.old.com/a");
+
+    let response = app.clone().oneshot(post("/api/links/retarget", retarget)).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["applied"], true);
+
+    let request = Request::builder().uri("/a").header(HOST, host.as_str()).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["location"], "https://wiki.new.com/a");
+
+    let request = Request::builder()
+        .uri(format!("/api/links/{}/revisions", a["id"]))
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let revisions: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(revisions.as_array().unwrap().len(), 1);
+    assert_eq!(revisions[0]["old_target"], "https://wiki.old.com/a");
+    assert_eq!(revisions[0]["version"], a["version"].as_i64().unwrap() + 1);
+
+    let response = app
+        .clone()
+        .oneshot(post("/api/links/retarget", serde_json::json!({"find": "(", "regex": true})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = Request::builder().uri("/api/links/-1/revisions").header(HOST, "lynx").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/retarget")
+        .header(HOST, "lynx")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(format!("find=example.com&replace=example.org&host={}", host)))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let page = String::from_utf8(body.to_vec()).unwrap();
+    assert!(page.contains("https://docs.example.org/b"));
+    assert!(page.contains("Apply 1 Change"));
+
+    for id in [&a["id"], &b["id"]] {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/links/{}", id))
+            .header(HOST, "lynx")
+            .header("if-match", "*")
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_api_resolve() {
+    let app = create_test_app().await;
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/links")
+        .header(HOST, "lynx")
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::json!({"host": host, "source": "/docs", "target": "https://example.com/docs"}).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let link: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let request = Request::builder()
+        .uri(format!("/api/resolve?host={}&path=docs/api/v1", host))
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let explanation: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(explanation["path"], "/docs/api/v1");
+    assert_eq!(explanation["target"], "https://example.com/docs");
+    assert_eq!(explanation["link"]["id"], link["id"]);
+    assert_eq!(explanation["kind"], "prefix");
+    assert_eq!(explanation["separator"], "/");
+    let tried: Vec<_> = explanation["candidates"].as_array().unwrap().iter().map(|c| c["source"].as_str().unwrap()).collect();
+    assert_eq!(tried, vec!["/docs/api/v1", "/docs/api", "/docs"]);
+
+    let request = Request::builder().uri("/api/resolve?path=/docs").header(HOST, "lynx").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = Request::builder()
+        .uri(format!("/edit/{}?test=/docs/api", link["id"]))
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let page = String::from_utf8(body.to_vec()).unwrap();
+    assert!(page.contains("through this link"));
+    assert!(page.contains("prefix match"));
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/links/{}", link["id"]))
+        .header(HOST, "lynx")
+        .header("if-match", "*")
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(request).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_read_only_mode() {
+    use chrono::Utc;
+    use lynx::models::Link;
+
+    // The database is never consulted: everything is served from the cache
+    let host = format!("test-{}", uuid::Uuid::new_v4());
+    let link = Link {
+        id: 1,
+        host: host.clone(),
+        source: "/docs".to_string(),
+        target: "https://docs.example.com".to_string(),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        hit_count: 0,
+        version: 1,
+    };
+    let state = create_test_state(test_config()).await;
+    state.cache.apply(vec![cache::CacheOp::Insert(link)]).await;
+    state.health.set_read_only(true);
+    let app = create_app(state);
+
+    let request = Request::builder()
+        .uri("/docs")
+        .header(HOST, host.as_str())
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert!(response.status().is_redirection());
+    assert_eq!(response.headers()["location"], "https://docs.example.com");
+
+    let request = Request::builder()
+        .uri(format!("/api/links?host={}", host))
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let links: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(links[0]["target"], "https://docs.example.com");
+
+    let request = Request::builder()
+        .uri("/")
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("Read-only mode"));
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/links")
+        .header(HOST, "lynx")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            serde_json::json!({"host": host, "source": "/new", "target": "https://example.com"}).to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(error["error"].as_str().unwrap().contains("read-only"));
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/delete/1")
+        .header(HOST, "lynx")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
 #[tokio::test]
 async fn test_redirector_logic() {
-    use lynx::redirector::*;
     use lynx::models::Link;
     use chrono::Utc;
     use std::collections::HashMap;
@@ -96,6 +1108,9 @@ async fn test_redirector_logic() {
         source: "/user/{id}".to_string(),
         target: "https://example.com/profile?id={id}".to_string(),
         created_at: Utc::now(),
+        updated_at: Utc::now(),
+        hit_count: 0,
+        version: 1,
     };
     cache.insert(("go".to_string(), "/user/{id}".to_string()), link);
     
@@ -109,3 +1124,137 @@ async fn test_redirector_logic() {
 // 3. Test redirect functionality with actual links
 // 4. Test parameter substitution
 // 5. Test uniqueness constraints
+
+#[tokio::test]
+async fn test_request_id() {
+    let app = create_test_app().await;
+
+    let request = Request::builder().uri("/healthz").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let generated = response.headers().get("x-request-id").expect("request id is generated");
+    assert!(uuid::Uuid::parse_str(generated.to_str().unwrap()).is_ok());
+
+    let request = Request::builder()
+        .uri("/healthz")
+        .header("x-request-id", "upstream-42")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.headers().get("x-request-id").unwrap(), "upstream-42");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_otlp_export() {
+    use axum::{body::Bytes, routing::post, Router};
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // A collector stub that keeps every export it's sent
+    let exports: Arc<Mutex<Vec<Bytes>>> = Arc::default();
+    let collector = Router::new().route(
+        "/v1/traces",
+        post({
+            let exports = exports.clone();
+            move |body: Bytes| async move { exports.lock().unwrap().push(body) }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, collector).await.unwrap() });
+
+    let provider = telemetry::tracer_provider(&endpoint).unwrap();
+    let subscriber = tracing_subscriber::registry().with(telemetry::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let state = create_test_state(test_config()).await;
+    let app = create_app(state.clone());
+    state.db.count_links(None).await.unwrap();
+
+    let request = Request::builder()
+        .uri("/docs")
+        .header(HOST, state.config.default_redirect_host.as_str())
+        .header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(request).await.unwrap();
+
+    tokio::task::spawn_blocking(move || telemetry::shutdown(provider)).await.unwrap();
+
+    let exported: Vec<u8> = exports.lock().unwrap().concat();
+    let contains = |needle: &[u8]| exported.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(b"count_links"), "database spans are exported");
+    assert!(contains(b"cache_lookup"), "cache lookup spans are exported");
+    // The request span continues the caller's trace
+    let trace_id: Vec<u8> = (0..16)
+        .map(|i| u8::from_str_radix(&"4bf92f3577b34da6a3ce929d0e0e4736"[i * 2..i * 2 + 2], 16).unwrap())
+        .collect();
+    assert!(contains(&trace_id), "incoming trace context is propagated");
+}
+
+#[tokio::test]
+async fn test_concurrent_migrations() {
+    let config = test_config();
+    if !config.database_url.starts_with("postgres") {
+        return;
+    }
+
+    // Migrate a scratch schema, so the shared test tables are left alone
+    let admin = sqlx::PgPool::connect(&config.database_url).await.unwrap();
+    let schema = format!("lynx_migrate_{}", uuid::Uuid::new_v4().simple());
+    sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&admin).await.unwrap();
+    let separator = if config.database_url.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}options=-c%20search_path%3D{}", config.database_url, separator, schema);
+
+    let replicas = (0..3).map(|_| async {
+        let db = database::Database::new(&url).await.unwrap();
+        db.migrate().await
+    });
+    for result in futures::future::join_all(replicas).await {
+        result.unwrap();
+    }
+
+    let db = database::Database::new(&url).await.unwrap();
+    assert_eq!(db.schema_status().await.unwrap(), database::SchemaStatus::default());
+
+    // A newer version has been here
+    sqlx::query(&format!(
+        "INSERT INTO {}._sqlx_migrations (version, description, success, checksum, execution_time) \
+         VALUES (999, 'from the future', true, '\\x00', 0)",
+        schema
+    ))
+    .execute(&admin)
+    .await
+    .unwrap();
+    assert_eq!(db.schema_status().await.unwrap().unknown, vec![999]);
+    assert!(db.migrate().await.is_err());
+
+    sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema)).execute(&admin).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_start_writable_with_snapshot_and_reachable_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cache.json");
+    let stale = models::Link {
+        id: -1,
+        host: "go".to_string(),
+        source: "/only-in-snapshot".to_string(),
+        target: "https://stale.example.com".to_string(),
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        hit_count: 0,
+        version: 1,
+    };
+    let links: cache::LinkMap = [(("go".to_string(), stale.source.clone()), stale)].into_iter().collect();
+    snapshot::save(&path, &links).await.unwrap();
+
+    let mut config = test_config();
+    config.auto_migrate = false;
+    config.snapshot_path = Some(path);
+    let (cache_status, health) = (cache::CacheStatusHandle::default(), health::Health::default());
+    let (_db, cache) = cli::open_storage(&config, &cache_status, &health).await.unwrap();
+
+    // The database was reachable, so the cache came from it and changes are accepted
+    assert!(!health.is_read_only());
+    assert!(!cache.snapshot().contains_key(&("go".to_string(), "/only-in-snapshot".to_string())));
+}